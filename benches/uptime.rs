@@ -0,0 +1,78 @@
+#![feature(test)]
+
+extern crate test;
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use drone_core::{thr, token::Token};
+use drone_time::{Tick, Uptime, UptimeCounter, UptimeDrv, UptimeOverflow};
+use test::{black_box, Bencher};
+
+struct Adapter;
+
+struct BenchTick;
+
+impl Tick for BenchTick {
+    const FREQ: u32 = 32768;
+}
+
+static COUNTER: AtomicU32 = AtomicU32::new(0);
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+struct BenchCounter;
+
+struct BenchOverflow;
+
+impl UptimeCounter<BenchTick, Adapter> for BenchCounter {
+    fn value(&self) -> u32 {
+        COUNTER.load(Ordering::Relaxed)
+    }
+}
+
+impl UptimeOverflow<Adapter> for BenchOverflow {
+    const MAX: u32 = 0xFFFF;
+
+    fn overflow_int_enable(&self) {}
+
+    fn is_pending_overflow(&self) -> bool {
+        PENDING.load(Ordering::Relaxed)
+    }
+
+    fn clear_pending_overflow(&self) {
+        PENDING.store(false, Ordering::Relaxed);
+    }
+}
+
+thr::pool! {
+    thread => Thr {};
+    local => ThrLocal {};
+    index => Thrs;
+    threads => { thr0 };
+}
+
+#[bench]
+fn now(b: &mut Bencher) {
+    let uptime = UptimeDrv::new(
+        BenchCounter,
+        BenchOverflow,
+        unsafe { Thr0::take() },
+        BenchTick,
+    );
+    b.iter(|| {
+        COUNTER.fetch_add(1, Ordering::Relaxed);
+        black_box(uptime.now())
+    });
+}
+
+#[bench]
+fn now_pending_overflow(b: &mut Bencher) {
+    let uptime = UptimeDrv::new(
+        BenchCounter,
+        BenchOverflow,
+        unsafe { Thr0::take() },
+        BenchTick,
+    );
+    b.iter(|| {
+        PENDING.store(true, Ordering::Relaxed);
+        black_box(uptime.now())
+    });
+}
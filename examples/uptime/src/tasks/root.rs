@@ -99,7 +99,7 @@ pub fn handler(reg: Regs, thr_init: ThrsInit) {
         alarm.spin(1000000);
     }
 
-    let mut watch = Watch::new(uptime.clone());
+    let watch = Watch::new(uptime.clone());
     watch.set(DateTime::new(2021, 1.into(), 1, 0, 0, 0), uptime.now());
 
     let f1 = alarm.sleep(TimeSpan::from_secs(6)).then(|_| {
@@ -1,4 +1,4 @@
-use crate::{Tick, TimeSpan};
+use crate::{Tick, TimeSpan, Uptime};
 use async_trait::async_trait;
 use core::convert::TryFrom;
 
@@ -17,7 +17,8 @@ pub enum AlarmTimerMode {
     /// The timer is always running between between 0 <= counter <= MAX, even when compare value is currently configured.
     AlwaysRunning,
     /// The alternate timer mode to be used when the timer is not always running between 0 <= counter <= MAX.
-    /// This mode is less robust when the duration exceeds a period, as jitter is introduced when setting up the next interrupt.
+    /// This mode is less robust when the duration exceeds a period, as jitter is introduced when setting up the next interrupt,
+    /// unless the timer is corrected against an uptime with `sleep_until()`.
     OneShotOnly,
 }
 
@@ -84,6 +85,21 @@ pub trait AlarmTimer<T: Tick, A: 'static>: Send {
         }
     }
 
+    /// Returns a future that resolves when `uptime` reaches `deadline`.
+    /// The remaining time is measured by `uptime` before each delay,
+    /// so that the jitter of setting up each delay does not accumulate.
+    ///
+    /// This function is only ever called if MODE == OneShotOnly.
+    async fn sleep_until(&mut self, uptime: &dyn Uptime<T>, deadline: TimeSpan<T>) {
+        loop {
+            let remaining = deadline - uptime.now();
+            if remaining <= TimeSpan::ZERO {
+                break;
+            }
+            self.delay(remaining.0.min(Self::MAX as i64) as u32).await;
+        }
+    }
+
     fn counter_add(base: u32, duration: u32) -> u32 {
         assert!(base <= Self::MAX);
         assert!(duration <= Self::MAX);
@@ -93,7 +109,10 @@ pub trait AlarmTimer<T: Tick, A: 'static>: Send {
 
 #[cfg(test)]
 pub mod fakes {
+    use alloc::sync::Arc;
+
     use super::*;
+    use crate::uptime::fakes::FakeUptime;
 
     pub struct Adapter;
 
@@ -103,6 +122,13 @@ pub mod fakes {
         pub(crate) compares: Vec<u32>,
     }
 
+    /// A one-shot timer where each delay overshoots by `jitter`.
+    pub struct FakeOneShotTimer {
+        pub(crate) uptime: Arc<FakeUptime<FakeTick>>,
+        pub(crate) jitter: i64,
+        pub(crate) delays: Vec<u32>,
+    }
+
     pub struct FakeTick;
     impl Tick for FakeTick {
         const FREQ: u32 = 1;
@@ -125,12 +151,30 @@ pub mod fakes {
             self.compares.push(compare);
         }
     }
+
+    #[async_trait]
+    impl AlarmTimer<FakeTick, Adapter> for FakeOneShotTimer {
+        const MAX: u32 = 9;
+        const MODE: AlarmTimerMode = AlarmTimerMode::OneShotOnly;
+
+        async fn delay(&mut self, duration: u32) {
+            assert!(duration <= Self::MAX);
+            self.delays.push(duration);
+            let now = self.uptime.now();
+            self.uptime
+                .set(now + TimeSpan::from_ticks(duration as i64 + self.jitter));
+        }
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::adapters::alarm::fakes::FakeAlarmTimer;
+    use crate::{
+        adapters::alarm::fakes::{FakeAlarmTimer, FakeOneShotTimer},
+        uptime::fakes::FakeUptime,
+    };
+    use alloc::sync::Arc;
     use futures_await_test::async_test;
 
     #[async_test]
@@ -166,6 +210,22 @@ pub mod tests {
         assert_eq!(vec![9, 4, 9, 5], timer.compares);
     }
 
+    #[async_test]
+    async fn sleep_until_corrects_jitter() {
+        let uptime = Arc::new(FakeUptime::new());
+        let mut timer = FakeOneShotTimer {
+            uptime: uptime.clone(),
+            jitter: 2,
+            delays: Vec::new(),
+        };
+
+        timer.sleep_until(&*uptime, TimeSpan::from_ticks(25)).await;
+
+        // Only the jitter of the last delay remains.
+        assert_eq!(vec![9, 9, 3], timer.delays);
+        assert_eq!(TimeSpan::from_ticks(27), uptime.now());
+    }
+
     #[test]
     fn sleep_drop() {
         let mut timer = FakeAlarmTimer {
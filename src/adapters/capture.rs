@@ -0,0 +1,16 @@
+use crate::Edge;
+
+/// A timer input capture channel.
+/// The channel must capture the counter of the timer that backs the uptime used with it.
+pub trait CaptureChannel<A>: Send + Sync + 'static {
+    /// Enable the capture interrupt.
+    fn capture_int_enable(&self);
+
+    /// Get the captured counter value and the captured edge, if a capture is pending.
+    /// The pending capture is cleared.
+    fn take_capture(&self) -> Option<(u32, Edge)>;
+
+    /// Get whether a capture was overwritten before it was taken.
+    /// The overcapture flag is cleared.
+    fn take_overcapture(&self) -> bool;
+}
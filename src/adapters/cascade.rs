@@ -0,0 +1,158 @@
+use core::marker::PhantomData;
+
+use crate::{Tick, UptimeCounter, UptimeOverflow};
+
+/// A 32-bit uptime counter made of two cascaded 16-bit timers.
+///
+/// The `lo` timer is the master that triggers the `hi` timer on each update event,
+/// i.e. `hi` counts the number of times that `lo` has wrapped.
+/// Both timers must have their auto-reload value configured to 0xFFFF.
+/// On stm32f4, `tim_cascade()` configures a pair of timers this way.
+///
+/// The trigger is resynchronized to the clock of the `hi` timer, so `hi` is incremented a few cycles after `lo` wraps.
+/// This lag must be less than `LAG` ticks of `lo`, and a read right after the wrap waits until it has passed.
+pub struct CascadeCounter<Lo, Hi> {
+    lo: Lo,
+    hi: Hi,
+}
+
+/// The overflow of a 32-bit uptime counter made of two cascaded 16-bit timers.
+///
+/// `ovf` must be the overflow of the `hi` timer in the corresponding `CascadeCounter`.
+pub struct CascadeOverflow<Ovf, A> {
+    ovf: Ovf,
+    adapter: PhantomData<A>,
+}
+
+/// The maximum number of `lo` ticks that `hi` is incremented after `lo` wraps.
+const LAG: u32 = 4;
+
+impl<Lo, Hi> CascadeCounter<Lo, Hi> {
+    /// Create a new cascaded counter from the `lo` master and `hi` slave counters.
+    pub fn new(lo: Lo, hi: Hi) -> Self {
+        Self { lo, hi }
+    }
+}
+
+impl<Ovf, A> CascadeOverflow<Ovf, A> {
+    /// Create a new cascaded overflow from the overflow of the `hi` slave timer.
+    pub fn new(ovf: Ovf) -> Self {
+        Self {
+            ovf,
+            adapter: PhantomData,
+        }
+    }
+}
+
+impl<T: Tick, A, Lo: UptimeCounter<T, A>, Hi: UptimeCounter<T, A>> UptimeCounter<T, A>
+    for CascadeCounter<Lo, Hi>
+{
+    fn value(&self) -> u32 {
+        loop {
+            let hi1 = self.hi.value();
+            let lo = self.lo.value();
+            let hi2 = self.hi.value();
+            // Retry if `lo` wrapped, or wrapped just before it was read and `hi` may not be incremented yet.
+            if hi1 == hi2 && lo >= LAG {
+                break hi1 << 16 | lo;
+            }
+        }
+    }
+}
+
+impl<Ovf: UptimeOverflow<A>, A: Send + Sync + 'static> UptimeOverflow<A>
+    for CascadeOverflow<Ovf, A>
+{
+    const MAX: u32 = 0xFFFF_FFFF;
+
+    fn overflow_int_enable(&self) {
+        self.ovf.overflow_int_enable();
+    }
+
+    fn is_pending_overflow(&self) -> bool {
+        self.ovf.is_pending_overflow()
+    }
+
+    fn clear_pending_overflow(&self) {
+        self.ovf.clear_pending_overflow();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use alloc::{sync::Arc, vec::Vec};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct Adapter;
+
+    struct TestTick;
+
+    impl Tick for TestTick {
+        const FREQ: u32 = 32768;
+    }
+
+    /// The 32-bit time, advanced by one tick for each read of the `lo` or `hi` counter.
+    struct FakeTimers {
+        reads: AtomicUsize,
+        ticks: Vec<u32>,
+        /// The number of ticks that `hi` is incremented after `lo` wraps.
+        lag: u32,
+    }
+
+    impl FakeTimers {
+        fn read(&self) -> u32 {
+            self.ticks[self.reads.fetch_add(1, Ordering::Relaxed)]
+        }
+    }
+
+    struct FakeLo(Arc<FakeTimers>);
+
+    struct FakeHi(Arc<FakeTimers>);
+
+    impl UptimeCounter<TestTick, Adapter> for FakeLo {
+        fn value(&self) -> u32 {
+            self.0.read() & 0xFFFF
+        }
+    }
+
+    impl UptimeCounter<TestTick, Adapter> for FakeHi {
+        fn value(&self) -> u32 {
+            (self.0.read() - self.0.lag) >> 16
+        }
+    }
+
+    #[test]
+    fn value() {
+        let timers = Arc::new(FakeTimers {
+            reads: AtomicUsize::new(0),
+            ticks: (0x1_FFFE..0x2_0010).collect(),
+            lag: 0,
+        });
+        let counter = CascadeCounter::new(FakeLo(timers.clone()), FakeHi(timers.clone()));
+        let value = || UptimeCounter::<TestTick, Adapter>::value(&counter);
+
+        // The reads are hi=0x1, lo=0xFFFF and hi=0x2 where `lo` wraps, so the read is retried,
+        // and then lo=0x2 is too close to the wrap, so it is retried again.
+        assert_eq!(0x2_0005, value());
+        assert_eq!(0x2_0008, value());
+        assert_eq!(12, timers.reads.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn value_with_lag() {
+        let timers = Arc::new(FakeTimers {
+            reads: AtomicUsize::new(0),
+            ticks: (0x1_FFFF..0x2_0010).collect(),
+            lag: 3,
+        });
+        let counter = CascadeCounter::new(FakeLo(timers.clone()), FakeHi(timers));
+        let value = || UptimeCounter::<TestTick, Adapter>::value(&counter);
+
+        // The reads are hi=0x1, lo=0x0 and hi=0x1 where `hi` is not yet incremented,
+        // so the read is retried until `lo` has passed the lag.
+        assert_eq!(0x2_0006, value());
+        assert_eq!(0x2_0009, value());
+    }
+}
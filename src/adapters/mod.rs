@@ -1,3 +1,5 @@
 pub(crate) mod alarm;
+pub(crate) mod capture;
+pub(crate) mod cascade;
 pub(crate) mod tick;
 pub(crate) mod uptime;
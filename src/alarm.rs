@@ -6,10 +6,10 @@ use core::{
     task::{Context, Poll, Waker},
 };
 
-use crate::{AlarmCounter, AlarmTimer, Tick, TimeSpan};
+use crate::{latch::Latch, AlarmCounter, AlarmTimer, AlarmTimerMode, Tick, TimeSpan, Uptime};
 use alloc::{collections::VecDeque, sync::Arc};
 use atomicbox::AtomicOptionBox;
-use drone_core::sync::Mutex;
+use drone_core::sync::{Mutex, MutexGuard};
 use futures::prelude::*;
 
 pub trait Alarm<T: Tick>: Send {
@@ -49,9 +49,29 @@ pub struct AlarmDrv<Cnt: AlarmCounter<T, A> + 'static, Tim: AlarmTimer<T, A>, T:
     timer: Arc<Mutex<Tim>>,
     running: Arc<AtomicOptionBox<Pin<Box<dyn Future<Output = ()>>>>>,
     subscriptions: Arc<Mutex<VecDeque<Subscription<T>>>>,
+    /// The uptime used to track the deadlines.
+    uptime: Option<Arc<dyn Uptime<T>>>,
+    schedule: Arc<Schedule<T>>,
     adapter: PhantomData<A>,
 }
 
+/// The upstamps at which the running timer was started and expires.
+type Schedule<T> = Latch<Option<(TimeSpan<T>, TimeSpan<T>)>>;
+
+/// An `AlarmDrv` that is suspended during a low power period.
+/// The alarm is resumed when this is dropped.
+pub struct Suspension<
+    'a,
+    Cnt: AlarmCounter<T, A> + 'static,
+    Tim: AlarmTimer<T, A> + 'static,
+    T: Tick,
+    A: Send + 'static,
+> {
+    alarm: &'a AlarmDrv<Cnt, Tim, T, A>,
+    /// The subscriptions are locked while suspended, so no timer can be started.
+    subscriptions: MutexGuard<'a, VecDeque<Subscription<T>>>,
+}
+
 pub struct Subscription<T: Tick> {
     /// The remaining duration until the future is resolved.
     remaining: TimeSpan<T>,
@@ -80,6 +100,19 @@ impl SubscriptionState {
     const WAKEABLE: u8 = 2;
     const COMPLETED: u8 = 3;
     const DROPPED: u8 = 4;
+
+    /// Wake the subscription without completing it, so that it is polled again.
+    /// This must only be called while the subscriptions are locked.
+    fn wake(&self) {
+        if self.value.load(Ordering::Acquire) != Self::WAKEABLE {
+            return;
+        }
+        if let Some(waker) = self.waker.take(Ordering::AcqRel) {
+            waker.wake_by_ref();
+            // If the subscription was polled meanwhile, its new waker is kept.
+            let _ = self.waker.try_store(waker, Ordering::AcqRel);
+        }
+    }
 }
 
 impl Future for SubscriptionGuard {
@@ -88,12 +121,15 @@ impl Future for SubscriptionGuard {
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let state = self.state.clone();
 
-        if state.value.compare_exchange(
-            SubscriptionState::PENDING_ADD,
-            SubscriptionState::ADDED,
-            Ordering::AcqRel,
-            Ordering::Relaxed,
-        ).is_ok()
+        if state
+            .value
+            .compare_exchange(
+                SubscriptionState::PENDING_ADD,
+                SubscriptionState::ADDED,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            )
+            .is_ok()
         {
             let mut appender = self.appender.take(Ordering::AcqRel).unwrap();
 
@@ -168,14 +204,81 @@ impl<
             timer: Arc::new(Mutex::new(timer)),
             running: Arc::new(AtomicOptionBox::new(None)),
             subscriptions: Arc::new(Mutex::new(VecDeque::new())),
+            uptime: None,
+            schedule: Arc::new(Latch::new(None)),
             adapter: PhantomData,
         }
     }
 
+    /// Track the deadlines of the alarm with `uptime`,
+    /// which must be driven by a timer that keeps running whenever the alarm is suspended.
+    ///
+    /// A `OneShotOnly` timer is then corrected against `uptime`, so that long sleeps end at their deadlines,
+    /// and the counter values of the alarm are those of `uptime`.
+    pub fn with_uptime<U: Uptime<T> + 'static>(mut self, uptime: Arc<U>) -> Self {
+        self.uptime = Some(uptime);
+        self
+    }
+
+    /// Get the upstamp at which the next subscription fires.
+    /// This is `None` if there are no subscriptions, or if no uptime is attached.
+    pub fn next_deadline(&self) -> Option<TimeSpan<T>> {
+        self.schedule.read().map(|(_, deadline)| deadline)
+    }
+
+    /// Suspend the alarm before entering a low power mode where the timer halts.
+    /// The running timer is stopped, and it is restarted when the returned `Suspension` is dropped,
+    /// after expiring the subscriptions whose deadlines were passed according to the uptime.
+    ///
+    /// This is `None` if no uptime is attached, or if the subscriptions are currently being modified,
+    /// and then the low power mode should not be entered.
+    pub fn suspend(&self) -> Option<Suspension<'_, Cnt, Tim, T, A>> {
+        self.uptime.as_ref()?;
+        let subscriptions = self.subscriptions.try_lock()?;
+
+        // Stop the running timer, releasing the lock on the timer.
+        self.running.take(Ordering::AcqRel);
+
+        Some(Suspension {
+            alarm: self,
+            subscriptions,
+        })
+    }
+
+    /// Record the upstamps of the timer that is started at `started` and expires after `duration`.
+    /// This must only be called while the subscriptions are locked.
+    fn schedule(schedule: &Schedule<T>, started: Option<TimeSpan<T>>, duration: TimeSpan<T>) {
+        unsafe { schedule.write(started.map(|started| (started, started + duration))) };
+    }
+
+    /// Get the upstamp at the alarm counter value `base`, if an uptime is attached.
+    fn upstamp_at(&self, base: u32) -> Option<TimeSpan<T>> {
+        let uptime = self.uptime.as_deref()?;
+        Some(match Self::reference(&self.uptime) {
+            // The counter values of the alarm are those of the uptime.
+            Some(reference) => reference.at(base),
+            None => {
+                let elapsed =
+                    (self.counter.value() as u64 + Tim::PERIOD - base as u64) % Tim::PERIOD;
+                uptime.now() - TimeSpan::from_ticks(elapsed as i64)
+            }
+        })
+    }
+
+    /// Get the uptime that the one-shot timer is corrected against, if any.
+    fn reference(uptime: &Option<Arc<dyn Uptime<T>>>) -> Option<&dyn Uptime<T>> {
+        match Tim::MODE {
+            AlarmTimerMode::OneShotOnly => uptime.as_deref(),
+            AlarmTimerMode::AlwaysRunning => None,
+        }
+    }
+
     async fn create_future(
         timer: Arc<Mutex<Tim>>,
         running: Arc<AtomicOptionBox<Pin<Box<dyn Future<Output = ()>>>>>,
         subscriptions: Arc<Mutex<VecDeque<Subscription<T>>>>,
+        uptime: Option<Arc<dyn Uptime<T>>>,
+        schedule: Arc<Schedule<T>>,
         base: u32,
         duration: TimeSpan<T>,
     ) {
@@ -183,7 +286,15 @@ impl<
             .try_lock()
             .expect("The timer must not be running when setting up a new timeout.");
         let timer = timer.clone();
-        t.sleep(base, duration)
+        let deadline = schedule.read().map(|(_, deadline)| deadline);
+        let reference = uptime.clone();
+        let sleep = async {
+            match (Self::reference(&reference), deadline) {
+                (Some(reference), Some(deadline)) => t.sleep_until(reference, deadline).await,
+                _ => t.sleep(base, duration).await,
+            }
+        };
+        sleep
             .then(move |_| {
                 let subscriptions = subscriptions.clone();
                 async move {
@@ -191,45 +302,36 @@ impl<
                     // Remove all subscriptions that are in the `DROPPED` state.
                     subs.remove_dropped();
 
-                    // Set the remaining time for each subscription.
-                    for s in subs.iter_mut() {
-                        s.remaining -= duration;
-
-                        if s.remaining.0 == 0 {
-                            // Wake the future for the subscription.
-                            let old = s
-                                .state
-                                .value
-                                .swap(SubscriptionState::COMPLETED, Ordering::AcqRel);
-                            if old == SubscriptionState::WAKEABLE {
-                                let waker = s.state.waker.take(Ordering::AcqRel).unwrap();
-                                waker.wake();
-                            } else if old == SubscriptionState::DROPPED {
-                                s.state
-                                    .value
-                                    .store(SubscriptionState::DROPPED, Ordering::Release);
-                            }
-                        }
-                    }
-
-                    // Remove all subscriptions that have remaining == 0.
-                    subs.retain(|x| x.remaining.0 > 0);
+                    // Complete the subscriptions that expire with the timer.
+                    subs.expire(duration);
 
                     if let Some(next) = subs.front() {
                         // Create a future for the next subscription in line.
 
-                        let base = Tim::counter_add(base, (duration.0 as u64 % Tim::PERIOD) as u32);
+                        let base = match Self::reference(&uptime) {
+                            // The base is not used when sleeping until the deadline,
+                            // and may be out of the counter range of the timer.
+                            Some(_) => base,
+                            None => {
+                                Tim::counter_add(base, (duration.0 as u64 % Tim::PERIOD) as u32)
+                            }
+                        };
                         let duration = next.remaining;
 
+                        // The next subscription starts when the previous one expires.
+                        Self::schedule(&schedule, deadline, duration);
                         let future = Self::create_future(
                             timer,
                             running.clone(),
                             subscriptions.clone(),
+                            uptime,
+                            schedule,
                             base,
                             duration,
                         );
                         running.store(Some(Box::new(future.boxed_local())), Ordering::AcqRel);
                     } else {
+                        Self::schedule(&schedule, None, TimeSpan::ZERO);
                         running.take(Ordering::AcqRel);
                     }
                 }
@@ -246,7 +348,11 @@ impl<
     > Alarm<T> for AlarmDrv<Cnt, Tim, T, A>
 {
     fn counter(&self) -> u32 {
-        self.counter.value()
+        match Self::reference(&self.uptime) {
+            // The one-shot timer counter is not running.
+            Some(uptime) => uptime.counter(),
+            None => self.counter.value(),
+        }
     }
 
     #[inline]
@@ -268,6 +374,9 @@ impl<
         let timer = self.timer.clone();
         let running = self.running.clone();
         let subscriptions = self.subscriptions.clone();
+        let uptime = self.uptime.clone();
+        let schedule = self.schedule.clone();
+        let started = self.upstamp_at(base);
         let appender = async move {
             let mut subs = subscriptions.lock().await;
 
@@ -281,10 +390,13 @@ impl<
             if index == 0 {
                 // It turns out that this subscription is the next in line.
 
+                Self::schedule(&schedule, started, duration);
                 let future = Self::create_future(
                     timer.clone(),
                     running.clone(),
                     subscriptions.clone(),
+                    uptime,
+                    schedule,
                     base,
                     duration,
                 );
@@ -302,9 +414,72 @@ impl<
     }
 }
 
+impl<
+        Cnt: AlarmCounter<T, A> + 'static,
+        Tim: AlarmTimer<T, A> + 'static,
+        T: Tick,
+        A: Send + 'static,
+    > Suspension<'_, Cnt, Tim, T, A>
+{
+    /// Get the upstamp at which the next subscription fires,
+    /// e.g. to program a wake-up timer before entering the low power mode.
+    pub fn deadline(&self) -> Option<TimeSpan<T>> {
+        self.alarm.next_deadline()
+    }
+
+    /// Resume the alarm after the low power period.
+    pub fn resume(self) {}
+}
+
+impl<
+        Cnt: AlarmCounter<T, A> + 'static,
+        Tim: AlarmTimer<T, A> + 'static,
+        T: Tick,
+        A: Send + 'static,
+    > Drop for Suspension<'_, Cnt, Tim, T, A>
+{
+    fn drop(&mut self) {
+        let alarm = self.alarm;
+        let uptime = alarm.uptime.clone().unwrap();
+        let now = uptime.now();
+
+        // Complete the subscriptions that expired while the timer was stopped.
+        if let Some((started, _)) = alarm.schedule.read() {
+            self.subscriptions.expire(now - started);
+        }
+        self.subscriptions.remove_dropped();
+
+        match self.subscriptions.front() {
+            Some(next) => {
+                let duration = next.remaining;
+                AlarmDrv::<Cnt, Tim, T, A>::schedule(&alarm.schedule, Some(now), duration);
+                let future = AlarmDrv::<Cnt, Tim, T, A>::create_future(
+                    alarm.timer.clone(),
+                    alarm.running.clone(),
+                    alarm.subscriptions.clone(),
+                    Some(uptime),
+                    alarm.schedule.clone(),
+                    alarm.counter.value(),
+                    duration,
+                );
+                alarm
+                    .running
+                    .store(Some(Box::new(future.boxed_local())), Ordering::AcqRel);
+
+                // The timer is only started when a subscription is polled.
+                for sub in self.subscriptions.iter() {
+                    sub.state.wake();
+                }
+            }
+            None => AlarmDrv::<Cnt, Tim, T, A>::schedule(&alarm.schedule, None, TimeSpan::ZERO),
+        }
+    }
+}
+
 trait VecDequeExt<T: Tick> {
     fn get_insert_index(&self, remaining: TimeSpan<T>) -> usize;
     fn remove_dropped(&mut self);
+    fn expire(&mut self, elapsed: TimeSpan<T>);
 }
 
 impl<T: Tick> VecDequeExt<T> for VecDeque<Subscription<T>> {
@@ -322,19 +497,73 @@ impl<T: Tick> VecDequeExt<T> for VecDeque<Subscription<T>> {
     fn remove_dropped(&mut self) {
         self.retain(|x| x.state.value.load(Ordering::Relaxed) != SubscriptionState::DROPPED);
     }
+
+    /// Subtract `elapsed` from the remaining time of each subscription,
+    /// and complete and remove the subscriptions that have no remaining time.
+    fn expire(&mut self, elapsed: TimeSpan<T>) {
+        for s in self.iter_mut() {
+            s.remaining -= elapsed;
+
+            if s.remaining.0 <= 0 {
+                // Wake the future for the subscription.
+                let old = s
+                    .state
+                    .value
+                    .swap(SubscriptionState::COMPLETED, Ordering::AcqRel);
+                if old == SubscriptionState::WAKEABLE {
+                    let waker = s.state.waker.take(Ordering::AcqRel).unwrap();
+                    waker.wake();
+                } else if old == SubscriptionState::DROPPED {
+                    s.state
+                        .value
+                        .store(SubscriptionState::DROPPED, Ordering::Release);
+                }
+            }
+        }
+
+        // Remove all subscriptions that have no remaining time.
+        self.retain(|x| x.remaining.0 > 0);
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
     use std::thread::spawn;
 
+    use async_trait::async_trait;
+    use core::sync::atomic::{AtomicBool, AtomicU32};
     use futures::future;
     use futures_await_test::async_test;
 
-    use crate::adapters::alarm::fakes::{FakeAlarmCounter, FakeAlarmTimer, FakeTick};
+    use crate::{
+        adapters::alarm::fakes::{
+            Adapter, FakeAlarmCounter, FakeAlarmTimer, FakeOneShotTimer, FakeTick,
+        },
+        uptime::fakes::FakeUptime,
+    };
 
     use super::*;
 
+    /// A timer that never fires.
+    struct HaltedAlarmTimer;
+
+    #[async_trait]
+    impl AlarmTimer<FakeTick, Adapter> for HaltedAlarmTimer {
+        const MAX: u32 = 9;
+
+        async fn next(&mut self, _compare: u32, _soon: bool) {
+            future::pending().await
+        }
+    }
+
+    struct CountingWaker(AtomicU32);
+
+    impl alloc::task::Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
     #[async_test]
     async fn whoot() {
         let counter = FakeAlarmCounter(4);
@@ -362,4 +591,161 @@ pub mod tests {
         // TODO: Find a way for the fake to actually schedule in correct order.
         assert_eq!(vec![1, 2, 3], fires.into_inner());
     }
+
+    #[async_test]
+    async fn one_shot_sleep_from() {
+        let uptime = Arc::new(FakeUptime::new());
+        uptime.set(TimeSpan::from_ticks(100));
+        let timer = FakeOneShotTimer {
+            uptime: uptime.clone(),
+            jitter: 1,
+            delays: Vec::new(),
+        };
+        let alarm = AlarmDrv::new(FakeAlarmCounter(0), timer, FakeTick).with_uptime(uptime.clone());
+
+        // Sleep relative to a counter value that was sampled 10 ticks ago.
+        let base = alarm.counter() - 10;
+        alarm.sleep_from(base, TimeSpan::from_ticks(30)).await;
+
+        // The jitter of the delays is corrected.
+        assert_eq!(TimeSpan::from_ticks(120), uptime.now());
+    }
+
+    /// A one-shot timer that yields once before each delay, so that subscriptions can queue up behind it.
+    struct YieldingOneShotTimer(FakeOneShotTimer);
+
+    #[async_trait]
+    impl AlarmTimer<FakeTick, Adapter> for YieldingOneShotTimer {
+        const MAX: u32 = 9;
+        const MODE: AlarmTimerMode = AlarmTimerMode::OneShotOnly;
+
+        async fn delay(&mut self, duration: u32) {
+            let mut yielded = false;
+            future::poll_fn(|cx| {
+                if yielded {
+                    Poll::Ready(())
+                } else {
+                    yielded = true;
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            })
+            .await;
+            self.0.delay(duration).await;
+        }
+    }
+
+    #[async_test]
+    async fn one_shot_queued() {
+        let uptime = Arc::new(FakeUptime::new());
+        uptime.set(TimeSpan::from_ticks(100));
+        let timer = YieldingOneShotTimer(FakeOneShotTimer {
+            uptime: uptime.clone(),
+            jitter: 0,
+            delays: Vec::new(),
+        });
+        let alarm = AlarmDrv::new(FakeAlarmCounter(0), timer, FakeTick).with_uptime(uptime.clone());
+
+        // The second subscription is queued behind the first one.
+        future::join(
+            alarm.sleep(TimeSpan::from_ticks(3)),
+            alarm.sleep(TimeSpan::from_ticks(5)),
+        )
+        .await;
+
+        assert_eq!(TimeSpan::from_ticks(105), uptime.now());
+    }
+
+    #[test]
+    fn suspend_resume() {
+        let uptime = Arc::new(FakeUptime::new());
+        let alarm = AlarmDrv::new(FakeAlarmCounter(0), HaltedAlarmTimer, FakeTick)
+            .with_uptime(uptime.clone());
+        let counter = Arc::new(CountingWaker(AtomicU32::new(0)));
+        let waker = counter.clone().into();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut first = alarm.sleep(TimeSpan::from_ticks(10));
+        let mut second = alarm.sleep(TimeSpan::from_ticks(30));
+        assert!(first.poll_unpin(&mut cx).is_pending());
+        assert!(second.poll_unpin(&mut cx).is_pending());
+        assert_eq!(Some(TimeSpan::from_ticks(10)), alarm.next_deadline());
+
+        // The first deadline passes while the timer is halted.
+        let suspension = alarm.suspend().unwrap();
+        assert_eq!(Some(TimeSpan::from_ticks(10)), suspension.deadline());
+        uptime.set(TimeSpan::from_ticks(20));
+        suspension.resume();
+
+        // The first subscription is completed, and the second one is woken to restart the timer.
+        assert_eq!(2, counter.0.load(Ordering::Relaxed));
+        assert!(first.poll_unpin(&mut cx).is_ready());
+        assert!(second.poll_unpin(&mut cx).is_pending());
+        assert_eq!(Some(TimeSpan::from_ticks(30)), alarm.next_deadline());
+    }
+
+    #[test]
+    fn deadline_from_alarm_counter() {
+        let uptime = Arc::new(FakeUptime::new());
+        uptime.set(TimeSpan::from_ticks(1000));
+        let alarm =
+            AlarmDrv::new(FakeAlarmCounter(4), HaltedAlarmTimer, FakeTick).with_uptime(uptime);
+        let waker = Arc::new(CountingWaker(AtomicU32::new(0))).into();
+        let mut cx = Context::from_waker(&waker);
+
+        // The base is a value of the alarm counter, which is not the uptime counter.
+        let mut sleep = alarm.sleep_from(1, TimeSpan::from_ticks(10));
+        assert!(sleep.poll_unpin(&mut cx).is_pending());
+        assert_eq!(Some(TimeSpan::from_ticks(1007)), alarm.next_deadline());
+    }
+
+    /// A timer that fires once `fire` is set, without waking.
+    struct GatedAlarmTimer(Arc<AtomicBool>);
+
+    #[async_trait]
+    impl AlarmTimer<FakeTick, Adapter> for GatedAlarmTimer {
+        const MAX: u32 = 9;
+
+        async fn next(&mut self, _compare: u32, _soon: bool) {
+            let fire = self.0.clone();
+            future::poll_fn(|_| {
+                if fire.load(Ordering::Relaxed) {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await
+        }
+    }
+
+    #[test]
+    fn resume_restarts_timer() {
+        let uptime = Arc::new(FakeUptime::new());
+        let fire = Arc::new(AtomicBool::new(false));
+        let alarm = AlarmDrv::new(FakeAlarmCounter(0), GatedAlarmTimer(fire.clone()), FakeTick)
+            .with_uptime(uptime.clone());
+        let counter = Arc::new(CountingWaker(AtomicU32::new(0)));
+        let waker = counter.clone().into();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut sleep = alarm.sleep(TimeSpan::from_ticks(10));
+        assert!(sleep.poll_unpin(&mut cx).is_pending());
+
+        // Nothing expires while suspended.
+        let suspension = alarm.suspend().unwrap();
+        uptime.set(TimeSpan::from_ticks(5));
+        fire.store(true, Ordering::Relaxed);
+        suspension.resume();
+
+        // The subscription is woken, so that it polls the restarted timer.
+        assert_eq!(1, counter.0.load(Ordering::Relaxed));
+        assert!(sleep.poll_unpin(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn suspend_requires_uptime() {
+        let alarm = AlarmDrv::new(FakeAlarmCounter(0), HaltedAlarmTimer, FakeTick);
+        assert!(alarm.suspend().is_none());
+    }
 }
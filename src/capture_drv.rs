@@ -0,0 +1,395 @@
+use alloc::{boxed::Box, sync::Arc};
+use atomicbox::AtomicOptionBox;
+use core::{
+    cell::UnsafeCell,
+    fmt::Debug,
+    marker::PhantomData,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+use drone_core::{fib, thr::prelude::*, thr::ThrToken};
+use futures::Stream;
+
+use crate::{CaptureChannel, Edge, Tick, TimeSpan, Uptime};
+
+/// An edge of an external signal captured at `upstamp`.
+pub struct Capture<T: Tick> {
+    /// The captured edge.
+    pub edge: Edge,
+    /// The upstamp at which the edge occured.
+    pub upstamp: TimeSpan<T>,
+}
+
+/// At least one capture was lost, either because it was overwritten in the timer
+/// or because the capture buffer was full.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CaptureOverrun;
+
+type CaptureResult<T> = Result<Capture<T>, CaptureOverrun>;
+
+/// Timestamps captured edges of external signals, such as a PPS or a sensor data-ready.
+///
+/// The captures are buffered in a ring of `N` entries by the capture interrupt,
+/// and consumed by a single `Captures` stream.
+pub struct CaptureDrv<U: Uptime<T>, Ch: CaptureChannel<A>, T: Tick, A, const N: usize> {
+    uptime: Arc<U>,
+    channel: Ch,
+    /// The ring of captures, written by the interrupt and read by the stream.
+    ring: [UnsafeCell<Option<CaptureResult<T>>>; N],
+    /// The number of captures written to the ring, only modified by the interrupt.
+    head: AtomicUsize,
+    /// The number of captures read from the ring, only modified by the stream.
+    tail: AtomicUsize,
+    /// Whether captures were lost since the last capture written to the ring.
+    overrun: AtomicBool,
+    /// Whether a `Captures` stream exists.
+    streaming: AtomicBool,
+    waker: AtomicOptionBox<Waker>,
+    adapter: PhantomData<A>,
+}
+
+/// The stream of captures from a `CaptureDrv`.
+pub struct Captures<'a, U: Uptime<T>, Ch: CaptureChannel<A>, T: Tick, A, const N: usize> {
+    drv: &'a CaptureDrv<U, Ch, T, A, N>,
+}
+
+unsafe impl<U: Uptime<T>, Ch: CaptureChannel<A>, T: Tick, A: Send + Sync, const N: usize> Sync
+    for CaptureDrv<U, Ch, T, A, N>
+{
+}
+
+impl<U, Ch, T, A, const N: usize> CaptureDrv<U, Ch, T, A, N>
+where
+    U: Uptime<T> + 'static,
+    Ch: CaptureChannel<A>,
+    T: Tick,
+    A: Send + Sync + 'static,
+{
+    /// Create a new capture driver that converts the captures of `channel` to upstamps of `uptime`.
+    pub fn new<CaptureInt: ThrToken>(
+        uptime: Arc<U>,
+        channel: Ch,
+        capture_int: CaptureInt,
+        _tick: T,
+    ) -> Arc<Self> {
+        assert!(N > 0, "The ring must hold at least one capture.");
+        let drv = Arc::new(Self {
+            uptime,
+            channel,
+            ring: [(); N].map(|_| UnsafeCell::new(None)),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            overrun: AtomicBool::new(false),
+            streaming: AtomicBool::new(false),
+            waker: AtomicOptionBox::new(None),
+            adapter: PhantomData,
+        });
+
+        let drv_weak = Arc::downgrade(&drv);
+        capture_int.add_fn(move || match drv_weak.upgrade() {
+            Some(drv) => {
+                drv.handle();
+                fib::Yielded(())
+            }
+            None => fib::Complete(()),
+        });
+        drv.channel.capture_int_enable();
+
+        drv
+    }
+
+    /// Get the stream of captures.
+    ///
+    /// # Panics
+    ///
+    /// If another stream of captures exists.
+    pub fn captures(&self) -> Captures<'_, U, Ch, T, A, N> {
+        assert!(
+            !self.streaming.swap(true, Ordering::Acquire),
+            "There can only be a single stream of captures."
+        );
+        Captures { drv: self }
+    }
+
+    /// Move the pending capture from the channel to the ring.
+    /// This must only be called from the capture interrupt.
+    pub(crate) fn handle(&self) {
+        if let Some((counter, edge)) = self.channel.take_capture() {
+            if self.channel.take_overcapture() {
+                self.overrun.store(true, Ordering::Relaxed);
+            }
+
+            let upstamp = self.uptime.at(counter);
+            if self.overrun.load(Ordering::Relaxed) && self.push(Err(CaptureOverrun)) {
+                self.overrun.store(false, Ordering::Relaxed);
+            }
+            if !self.overrun.load(Ordering::Relaxed) && !self.push(Ok(Capture { edge, upstamp })) {
+                self.overrun.store(true, Ordering::Relaxed);
+            }
+
+            if let Some(waker) = self.waker.take(Ordering::AcqRel) {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Write `capture` to the ring, and get whether there was room for it.
+    fn push(&self, capture: CaptureResult<T>) -> bool {
+        let head = self.head.load(Ordering::Relaxed);
+        if head.wrapping_sub(self.tail.load(Ordering::Acquire)) == N {
+            return false;
+        }
+
+        // The slot is not read by the stream until `head` is advanced.
+        unsafe { *self.ring[head % N].get() = Some(capture) };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Read the oldest capture from the ring.
+    fn pop(&self) -> Option<CaptureResult<T>> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail == self.head.load(Ordering::Acquire) {
+            return None;
+        }
+
+        // The slot is not written by the interrupt until `tail` is advanced.
+        let capture = unsafe { (*self.ring[tail % N].get()).take() };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        capture
+    }
+}
+
+impl<U, Ch, T, A, const N: usize> Stream for Captures<'_, U, Ch, T, A, N>
+where
+    U: Uptime<T> + 'static,
+    Ch: CaptureChannel<A>,
+    T: Tick,
+    A: Send + Sync + 'static,
+{
+    type Item = CaptureResult<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(capture) = self.drv.pop() {
+            return Poll::Ready(Some(capture));
+        }
+
+        self.drv
+            .waker
+            .store(Some(Box::new(cx.waker().clone())), Ordering::AcqRel);
+
+        // A capture may have been written before the waker was stored.
+        match self.drv.pop() {
+            Some(capture) => Poll::Ready(Some(capture)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<U: Uptime<T>, Ch: CaptureChannel<A>, T: Tick, A, const N: usize> Drop
+    for Captures<'_, U, Ch, T, A, N>
+{
+    fn drop(&mut self) {
+        self.drv.streaming.store(false, Ordering::Release);
+    }
+}
+
+impl<T: Tick> Copy for Capture<T> {}
+
+impl<T: Tick> Clone for Capture<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Tick> PartialEq for Capture<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.edge == other.edge && self.upstamp == other.upstamp
+    }
+}
+
+impl<T: Tick> Debug for Capture<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Capture")
+            .field("edge", &self.edge)
+            .field("upstamp", &self.upstamp)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use alloc::vec::Vec;
+    use drone_core::{sync::Mutex, thr, token::Token};
+    use futures::{FutureExt, StreamExt};
+
+    use crate::uptime::fakes::FakeUptime;
+
+    use super::*;
+
+    struct Adapter;
+
+    struct TestTick;
+
+    impl Tick for TestTick {
+        const FREQ: u32 = 32768;
+    }
+
+    struct FakeChannel {
+        captures: Mutex<Vec<(u32, Edge)>>,
+        overcapture: AtomicBool,
+    }
+
+    impl FakeChannel {
+        fn capture(&self, counter: u32, edge: Edge) {
+            let mut captures = self.captures.try_lock().unwrap();
+            if !captures.is_empty() {
+                self.overcapture.store(true, Ordering::Relaxed);
+                captures.clear();
+            }
+            captures.push((counter, edge));
+        }
+    }
+
+    impl CaptureChannel<Adapter> for Arc<FakeChannel> {
+        fn capture_int_enable(&self) {}
+
+        fn take_capture(&self) -> Option<(u32, Edge)> {
+            self.captures.try_lock().unwrap().pop()
+        }
+
+        fn take_overcapture(&self) -> bool {
+            self.overcapture.swap(false, Ordering::Relaxed)
+        }
+    }
+
+    thr::pool! {
+        thread => Thr {};
+        local => ThrLocal {};
+        index => Thrs;
+        threads => { thr0 };
+    }
+
+    type FakeCaptureDrv<const N: usize> =
+        CaptureDrv<FakeUptime<TestTick>, Arc<FakeChannel>, TestTick, Adapter, N>;
+
+    fn create<const N: usize>() -> (
+        Arc<FakeUptime<TestTick>>,
+        Arc<FakeChannel>,
+        Arc<FakeCaptureDrv<N>>,
+    ) {
+        let uptime = Arc::new(FakeUptime::new());
+        let channel = Arc::new(FakeChannel {
+            captures: Mutex::new(Vec::new()),
+            overcapture: AtomicBool::new(false),
+        });
+        let thread = unsafe { Thr0::take() };
+        let drv = CaptureDrv::new(uptime.clone(), channel.clone(), thread, TestTick);
+        (uptime, channel, drv)
+    }
+
+    fn capture(edge: Edge, upstamp: i64) -> Option<CaptureResult<TestTick>> {
+        Some(Ok(Capture {
+            edge,
+            upstamp: TimeSpan::from_ticks(upstamp),
+        }))
+    }
+
+    #[test]
+    fn captures() {
+        let (uptime, channel, drv) = create::<4>();
+        let mut captures = drv.captures();
+        assert_eq!(None, captures.next().now_or_never());
+
+        uptime.set(TimeSpan::from_ticks(1000));
+        channel.capture(990, Edge::Rising);
+        drv.handle();
+        channel.capture(995, Edge::Falling);
+        drv.handle();
+
+        assert_eq!(
+            Some(capture(Edge::Rising, 990)),
+            captures.next().now_or_never()
+        );
+        assert_eq!(
+            Some(capture(Edge::Falling, 995)),
+            captures.next().now_or_never()
+        );
+        assert_eq!(None, captures.next().now_or_never());
+    }
+
+    #[test]
+    fn overruns() {
+        let (uptime, channel, drv) = create::<2>();
+        let mut captures = drv.captures();
+        uptime.set(TimeSpan::from_ticks(1000));
+
+        // The first capture is overwritten in the timer.
+        channel.capture(1, Edge::Rising);
+        channel.capture(2, Edge::Rising);
+        drv.handle();
+
+        // The ring is full.
+        channel.capture(3, Edge::Rising);
+        drv.handle();
+        channel.capture(4, Edge::Rising);
+        drv.handle();
+
+        assert_eq!(
+            Some(Some(Err(CaptureOverrun))),
+            captures.next().now_or_never()
+        );
+        assert_eq!(
+            Some(capture(Edge::Rising, 2)),
+            captures.next().now_or_never()
+        );
+
+        channel.capture(5, Edge::Rising);
+        drv.handle();
+
+        assert_eq!(
+            Some(Some(Err(CaptureOverrun))),
+            captures.next().now_or_never()
+        );
+        assert_eq!(
+            Some(capture(Edge::Rising, 5)),
+            captures.next().now_or_never()
+        );
+        assert_eq!(None, captures.next().now_or_never());
+    }
+
+    struct CountingWaker(AtomicUsize);
+
+    impl alloc::task::Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn wake() {
+        let (_, channel, drv) = create::<2>();
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = counter.clone().into();
+        let mut cx = Context::from_waker(&waker);
+        let mut captures = drv.captures();
+        assert!(captures.poll_next_unpin(&mut cx).is_pending());
+
+        channel.capture(0, Edge::Falling);
+        drv.handle();
+        assert_eq!(1, counter.0.load(Ordering::Relaxed));
+        assert_eq!(
+            Poll::Ready(capture(Edge::Falling, 0)),
+            captures.poll_next_unpin(&mut cx)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn single_stream() {
+        let (_, _, drv) = create::<2>();
+        let _captures = drv.captures();
+        drv.captures();
+    }
+}
@@ -96,6 +96,11 @@ impl DateTime {
         Self(timestamp)
     }
 
+    /// Get the number of seconds since the unix epoch.
+    pub const fn unixtimestamp(&self) -> u32 {
+        self.0
+    }
+
     /// Get the date part without the time component.
     pub const fn date(&self) -> Self {
         Self((self.0 / SECONDS_PER_DAY) * SECONDS_PER_DAY)
@@ -180,7 +185,7 @@ impl<T: Tick> Sub<TimeSpan<T>> for DateTime {
     }
 }
 
-fn days_in_month(year: u16, month: Month) -> u8 {
+pub(crate) fn days_in_month(year: u16, month: Month) -> u8 {
     if is_leap_year(year) && month == Month::February {
         29
     } else {
@@ -0,0 +1,409 @@
+use crate::{datetime::days_in_month, DateTime, Edge, Tick, TimeSpan};
+
+/// The number of bits transmitted each minute, second 59 is left out to mark the minute.
+const FRAME_BITS: u8 = 59;
+/// The number of bits transmitted in a minute with a leap second, which adds a 0 in second 59.
+const LEAP_FRAME_BITS: u8 = 60;
+
+/// A decoder for the DCF77 time signal.
+///
+/// Only DCF77 is supported, the MSF signal uses a different pulse and frame format.
+///
+/// The decoder is fed with the edges of the demodulated signal, e.g. as captured by `Uptime::at()`.
+/// The signal is assumed to be high during the carrier reduction, i.e. a `Rising` edge marks the start of a second,
+/// and the following `Falling` edge the end of its 100ms (0) or 200ms (1) pulse.
+pub struct Dcf77Decoder<T: Tick> {
+    /// The upstamp of the start of the last second.
+    second_start: Option<TimeSpan<T>>,
+    /// The bits received since the last minute marker.
+    bits: u64,
+    /// The number of bits received since the last minute marker, `None` if no minute marker is seen yet.
+    count: Option<u8>,
+}
+
+/// A decoded DCF77 minute.
+pub struct Dcf77Time<T: Tick> {
+    /// The UTC time at the start of the minute.
+    pub datetime: DateTime,
+    /// The upstamp of the start of the minute.
+    pub upstamp: TimeSpan<T>,
+    /// Whether the transmitted local time is summer time (CEST) or not (CET).
+    pub summer_time: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Dcf77Error {
+    /// A pulse was neither a 0 nor a 1.
+    InvalidPulse,
+    /// The time between two seconds was neither a second nor a minute marker.
+    InvalidInterval,
+    /// The minute marker was received before or after all bits of the minute.
+    /// A minute with a leap second is only accepted when the leap second is announced.
+    InvalidLength,
+    /// A parity check failed.
+    Parity,
+    /// The received bits do not form a valid time.
+    InvalidFrame,
+}
+
+impl<T: Tick> Dcf77Decoder<T> {
+    const ZERO_MIN: TimeSpan<T> = TimeSpan::from_millis(40);
+    const ONE_MIN: TimeSpan<T> = TimeSpan::from_millis(140);
+    const ONE_MAX: TimeSpan<T> = TimeSpan::from_millis(250);
+    const SECOND_MIN: TimeSpan<T> = TimeSpan::from_millis(900);
+    const SECOND_MAX: TimeSpan<T> = TimeSpan::from_millis(1100);
+    const MINUTE_MIN: TimeSpan<T> = TimeSpan::from_millis(1900);
+    const MINUTE_MAX: TimeSpan<T> = TimeSpan::from_millis(2100);
+
+    /// Create a new `Dcf77Decoder`.
+    pub const fn new() -> Self {
+        Self {
+            second_start: None,
+            bits: 0,
+            count: None,
+        }
+    }
+
+    /// Feed an `edge` of the signal that occured at `upstamp`.
+    /// A time is returned when the minute marker following a complete and valid minute is received.
+    pub fn edge(
+        &mut self,
+        edge: Edge,
+        upstamp: TimeSpan<T>,
+    ) -> Result<Option<Dcf77Time<T>>, Dcf77Error> {
+        match edge {
+            Edge::Rising => self.second(upstamp),
+            Edge::Falling => self.pulse(upstamp).map(|_| None),
+        }
+    }
+
+    fn second(&mut self, upstamp: TimeSpan<T>) -> Result<Option<Dcf77Time<T>>, Dcf77Error> {
+        let interval = match self.second_start.replace(upstamp) {
+            Some(last) => upstamp - last,
+            None => return Ok(None),
+        };
+
+        if (Self::SECOND_MIN..=Self::SECOND_MAX).contains(&interval) {
+            Ok(None)
+        } else if (Self::MINUTE_MIN..=Self::MINUTE_MAX).contains(&interval) {
+            // The missing pulse in second 59 marks the start of a new minute.
+            let bits = self.bits;
+            let count = self.count.replace(0);
+            self.bits = 0;
+
+            match count {
+                // A leap second is announced by bit 19, and inserted as a 0 in second 59.
+                Some(count)
+                    if count == FRAME_BITS
+                        || count == LEAP_FRAME_BITS && bit(bits, 19) && !bit(bits, 59) =>
+                {
+                    let (datetime, summer_time) = Self::decode(bits)?;
+                    Ok(Some(Dcf77Time {
+                        datetime,
+                        upstamp,
+                        summer_time,
+                    }))
+                }
+                Some(_) => Err(Dcf77Error::InvalidLength),
+                None => Ok(None),
+            }
+        } else {
+            self.reset();
+            Err(Dcf77Error::InvalidInterval)
+        }
+    }
+
+    fn pulse(&mut self, upstamp: TimeSpan<T>) -> Result<(), Dcf77Error> {
+        let width = match self.second_start {
+            Some(start) => upstamp - start,
+            None => return Ok(()),
+        };
+
+        let bit: u64 = if (Self::ZERO_MIN..Self::ONE_MIN).contains(&width) {
+            0
+        } else if (Self::ONE_MIN..=Self::ONE_MAX).contains(&width) {
+            1
+        } else {
+            self.reset();
+            return Err(Dcf77Error::InvalidPulse);
+        };
+
+        if let Some(count) = self.count {
+            if count == LEAP_FRAME_BITS {
+                self.reset();
+                return Err(Dcf77Error::InvalidLength);
+            }
+            self.bits |= bit << count;
+            self.count = Some(count + 1);
+        }
+
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.bits = 0;
+        self.count = None;
+    }
+
+    fn decode(bits: u64) -> Result<(DateTime, bool), Dcf77Error> {
+        // Bit 0 is always 0 and bit 20 (start of time) is always 1.
+        if bit(bits, 0) || !bit(bits, 20) {
+            return Err(Dcf77Error::InvalidFrame);
+        }
+
+        if !even_parity(bits, 21, 29) || !even_parity(bits, 29, 36) || !even_parity(bits, 36, 59) {
+            return Err(Dcf77Error::Parity);
+        }
+
+        let summer_time = match (bit(bits, 17), bit(bits, 18)) {
+            (true, false) => true,
+            (false, true) => false,
+            _ => return Err(Dcf77Error::InvalidFrame),
+        };
+
+        let minute = bcd(bits, 21, 7)?;
+        let hour = bcd(bits, 29, 6)?;
+        let day = bcd(bits, 36, 6)?;
+        let month = bcd(bits, 45, 5)?;
+        let year = bcd(bits, 50, 8)?;
+
+        if minute > 59 || hour > 23 || month == 0 || month > 12 {
+            return Err(Dcf77Error::InvalidFrame);
+        }
+        let year = 2000 + year as u16;
+        if day == 0 || day > days_in_month(year, month.into()) {
+            return Err(Dcf77Error::InvalidFrame);
+        }
+
+        // The transmitted time is the local time in Germany, CET (UTC+1) or CEST (UTC+2).
+        let local = DateTime::new(year, month.into(), day, hour, minute, 0);
+        let offset = if summer_time { 2 } else { 1 };
+
+        Ok((local - TimeSpan::<T>::from_hours(offset), summer_time))
+    }
+}
+
+impl<T: Tick> Default for Dcf77Decoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn bit(bits: u64, index: u8) -> bool {
+    bits & (1 << index) != 0
+}
+
+/// Get whether the bits `first..end` have even parity.
+fn even_parity(bits: u64, first: u8, end: u8) -> bool {
+    let mask = ((1u64 << (end - first)) - 1) << first;
+    (bits & mask).count_ones() % 2 == 0
+}
+
+/// Decode the BCD value of `len` bits starting at `first`.
+fn bcd(bits: u64, first: u8, len: u8) -> Result<u8, Dcf77Error> {
+    let value = ((bits >> first) & ((1 << len) - 1)) as u8;
+    let ones = value & 0x0F;
+    let tens = value >> 4;
+    if ones > 9 || tens > 9 {
+        Err(Dcf77Error::InvalidFrame)
+    } else {
+        Ok(tens * 10 + ones)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::Month;
+
+    use super::*;
+
+    struct TestTick;
+
+    impl Tick for TestTick {
+        const FREQ: u32 = 32768;
+    }
+
+    fn to_bcd(value: u8) -> u64 {
+        ((value / 10) << 4 | (value % 10)) as u64
+    }
+
+    fn parity(value: u64) -> u64 {
+        (value.count_ones() % 2) as u64
+    }
+
+    fn encode(year: u8, month: u8, day: u8, hour: u8, minute: u8, summer_time: bool) -> u64 {
+        let minute = to_bcd(minute);
+        let hour = to_bcd(hour);
+        let date = to_bcd(day) | 5 << 6 | to_bcd(month) << 9 | to_bcd(year) << 14;
+
+        let zone = if summer_time { 1 << 17 } else { 1 << 18 };
+        zone | 1 << 20
+            | minute << 21
+            | parity(minute) << 28
+            | hour << 29
+            | parity(hour) << 35
+            | date << 36
+            | parity(date) << 58
+    }
+
+    /// Feed a full minute of `len` bits starting at `start`, preceded by a minute marker.
+    fn feed(
+        decoder: &mut Dcf77Decoder<TestTick>,
+        bits: u64,
+        len: u8,
+        start: TimeSpan<TestTick>,
+    ) -> Vec<Result<Option<Dcf77Time<TestTick>>, Dcf77Error>> {
+        let mut results = Vec::new();
+        let second = TimeSpan::<TestTick>::from_secs(1);
+
+        let mut pulse =
+            |decoder: &mut Dcf77Decoder<TestTick>, at: TimeSpan<TestTick>, one: bool| {
+                let width = TimeSpan::from_millis(if one { 200 } else { 100 });
+                results.push(decoder.edge(Edge::Rising, at));
+                results.push(decoder.edge(Edge::Falling, at + width));
+            };
+
+        // Second 58 of the previous minute.
+        pulse(decoder, start - second - second, false);
+
+        let mut at = start;
+        for index in 0..len {
+            pulse(decoder, at, bit(bits, index));
+            at += second;
+        }
+
+        // The last second is left out and the next minute starts.
+        results.push(decoder.edge(Edge::Rising, at + second));
+
+        results
+    }
+
+    #[test]
+    fn decode() {
+        let mut decoder = Dcf77Decoder::new();
+        let start = TimeSpan::from_secs(100);
+
+        let mut results = feed(
+            &mut decoder,
+            encode(21, 1, 8, 10, 39, false),
+            FRAME_BITS,
+            start,
+        );
+
+        let time = results.pop().unwrap().unwrap().unwrap();
+        assert_eq!(
+            DateTime::new(2021, Month::January, 8, 9, 39, 0),
+            time.datetime
+        );
+        assert_eq!(start + TimeSpan::from_secs(60), time.upstamp);
+        assert!(!time.summer_time);
+        assert!(results.iter().all(|x| matches!(x, Ok(None))));
+    }
+
+    #[test]
+    fn decode_summer_time() {
+        let mut decoder = Dcf77Decoder::new();
+
+        let mut results = feed(
+            &mut decoder,
+            encode(21, 7, 1, 0, 5, true),
+            FRAME_BITS,
+            TimeSpan::from_secs(100),
+        );
+
+        let time = results.pop().unwrap().unwrap().unwrap();
+        assert_eq!(
+            DateTime::new(2021, Month::June, 30, 22, 5, 0),
+            time.datetime
+        );
+        assert!(time.summer_time);
+    }
+
+    #[test]
+    fn parity_error() {
+        let mut decoder = Dcf77Decoder::new();
+
+        // Flip the 1 minute bit without updating the parity.
+        let bits = encode(21, 1, 8, 10, 39, false) ^ 1 << 21;
+        let mut results = feed(&mut decoder, bits, FRAME_BITS, TimeSpan::from_secs(100));
+
+        assert_eq!(Dcf77Error::Parity, results.pop().unwrap().err().unwrap());
+    }
+
+    #[test]
+    fn leap_second() {
+        let start = TimeSpan::from_secs(100);
+        // The leap second is inserted before 01:00 CET, i.e. at 23:59:60 UTC.
+        let bits = encode(17, 1, 1, 0, 59, false) | 1 << 19;
+
+        let mut decoder = Dcf77Decoder::new();
+        let mut results = feed(&mut decoder, bits, LEAP_FRAME_BITS, start);
+
+        let time = results.pop().unwrap().unwrap().unwrap();
+        assert_eq!(
+            DateTime::new(2016, Month::December, 31, 23, 59, 0),
+            time.datetime
+        );
+        assert_eq!(start + TimeSpan::from_secs(61), time.upstamp);
+        assert!(results.iter().all(|x| matches!(x, Ok(None))));
+
+        // An extra second is only accepted when a leap second is announced.
+        let mut decoder = Dcf77Decoder::new();
+        let mut results = feed(
+            &mut decoder,
+            encode(17, 1, 1, 0, 59, false),
+            LEAP_FRAME_BITS,
+            start,
+        );
+        assert_eq!(
+            Dcf77Error::InvalidLength,
+            results.pop().unwrap().err().unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_day() {
+        let mut decoder = Dcf77Decoder::new();
+
+        let mut results = feed(
+            &mut decoder,
+            encode(21, 2, 29, 10, 39, false),
+            FRAME_BITS,
+            TimeSpan::from_secs(100),
+        );
+        assert_eq!(
+            Dcf77Error::InvalidFrame,
+            results.pop().unwrap().err().unwrap()
+        );
+
+        // February 29 exists in a leap year.
+        let mut results = feed(
+            &mut decoder,
+            encode(24, 2, 29, 10, 39, false),
+            FRAME_BITS,
+            TimeSpan::from_secs(200),
+        );
+        assert!(results.pop().unwrap().unwrap().is_some());
+    }
+
+    #[test]
+    fn missing_second() {
+        let mut decoder = Dcf77Decoder::<TestTick>::new();
+
+        decoder.edge(Edge::Rising, TimeSpan::from_secs(0)).unwrap();
+        decoder.edge(Edge::Rising, TimeSpan::from_secs(2)).unwrap();
+        decoder.edge(Edge::Rising, TimeSpan::from_secs(3)).unwrap();
+        decoder
+            .edge(Edge::Falling, TimeSpan::from_millis(3100))
+            .unwrap();
+
+        // A second without a pulse is taken as a minute marker.
+        assert_eq!(
+            Dcf77Error::InvalidLength,
+            decoder
+                .edge(Edge::Rising, TimeSpan::from_secs(5))
+                .err()
+                .unwrap()
+        );
+    }
+}
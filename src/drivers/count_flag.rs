@@ -0,0 +1,279 @@
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+/// A register with a flag that is cleared when the register is read, such as the SysTick COUNTFLAG.
+pub(crate) trait ClearOnRead {
+    /// The flag bit in the register.
+    const FLAG: u32;
+
+    /// Read the register into `record`.
+    ///
+    /// A reader that preempts the current thread must be able to `peek()` the read value
+    /// as soon as the register is read, i.e. also before it is stored in `record.value`.
+    /// The read value must never be 0.
+    fn read(&self, record: &Record);
+
+    /// Get the value that the preempted thread owning `record` has read, `None` if it is not read yet.
+    fn peek(&self, record: &Record) -> Option<u32>;
+
+    /// Called between each step of the protocol, so that tests can preempt it.
+    #[inline]
+    fn preempt(&self) {}
+}
+
+/// A register read in progress.
+pub(crate) struct Record {
+    /// The read value, 0 if not stored yet.
+    pub(crate) value: AtomicU32,
+    /// Data for `ClearOnRead::peek()` to find the value before it is stored.
+    pub(crate) context: AtomicUsize,
+}
+
+/// Latches the flag of a `ClearOnRead` register without disabling interrupts.
+///
+/// A reader takes ownership of the latch by storing a pointer to its `Record` in `state` before reading the register.
+/// A reader that preempts the owner peeks the value that the owner has read, if any,
+/// and latches the flag on behalf of the owner if it was set.
+/// Otherwise it takes over the ownership, reads the register itself,
+/// and hands the ownership back to the preempted owner when done.
+/// Either way, a flag that is cleared by a read is always latched before anyone can miss it.
+pub(crate) struct CountFlagLatch {
+    /// `IDLE`, `LATCHED`, or a pointer to the `Record` of the current owner.
+    state: AtomicUsize,
+}
+
+const IDLE: usize = 0;
+const LATCHED: usize = 1;
+
+impl Record {
+    const fn new() -> Self {
+        Self {
+            value: AtomicU32::new(0),
+            context: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl CountFlagLatch {
+    pub(crate) const fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(IDLE),
+        }
+    }
+
+    /// Get whether the flag is latched, reading the register if needed.
+    pub(crate) fn is_pending<R: ClearOnRead>(&self, register: &R) -> bool {
+        let record = Record::new();
+        let me = &record as *const Record as usize;
+
+        register.preempt();
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            match state {
+                LATCHED => return true,
+                IDLE => {}
+                owner => {
+                    // We have preempted the owner, which may have cleared the flag without latching it yet.
+                    let owner = unsafe { &*(owner as *const Record) };
+                    if matches!(register.peek(owner), Some(value) if value & R::FLAG != 0) {
+                        register.preempt();
+                        match self.state.compare_exchange(
+                            state,
+                            LATCHED,
+                            Ordering::AcqRel,
+                            Ordering::Acquire,
+                        ) {
+                            Ok(_) => return true,
+                            Err(current) => {
+                                state = current;
+                                continue;
+                            }
+                        }
+                    }
+                }
+            }
+
+            register.preempt();
+            match self
+                .state
+                .compare_exchange(state, me, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => break,
+                Err(current) => state = current,
+            }
+        }
+
+        register.preempt();
+        register.read(&record);
+        register.preempt();
+
+        let next = if record.value.load(Ordering::Acquire) & R::FLAG != 0 {
+            LATCHED
+        } else {
+            // Hand the ownership back to the owner that we may have preempted.
+            state
+        };
+        // This fails if a preempting reader has latched the flag on our behalf.
+        self.state
+            .compare_exchange(me, next, Ordering::AcqRel, Ordering::Acquire)
+            .ok();
+        register.preempt();
+
+        // The record must not be referenced when it goes out of scope.
+        debug_assert_ne!(me, self.state.load(Ordering::Relaxed));
+
+        self.state.load(Ordering::Acquire) == LATCHED
+    }
+
+    /// Clear the latched flag.
+    pub(crate) fn clear(&self) {
+        self.state
+            .compare_exchange(LATCHED, IDLE, Ordering::AcqRel, Ordering::Relaxed)
+            .ok();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use core::{
+        cell::{Cell, RefCell},
+        sync::atomic::AtomicBool,
+    };
+
+    use super::*;
+
+    const ENABLE: u32 = 1 << 0;
+    const COUNTFLAG: u32 = 1 << 16;
+
+    #[derive(Clone, Copy)]
+    enum Action {
+        Wrap,
+        Check,
+        WrapCheck,
+    }
+
+    struct FakeCtrl {
+        latch: CountFlagLatch,
+        flag: AtomicBool,
+        /// The number of times the counter wrapped.
+        wraps: Cell<u32>,
+        /// The number of wraps that were handled.
+        handled: Cell<u32>,
+        /// The number of steps.
+        steps: Cell<u32>,
+        /// The steps to preempt.
+        preempt: RefCell<[Option<(u32, Action)>; 2]>,
+    }
+
+    impl ClearOnRead for FakeCtrl {
+        const FLAG: u32 = COUNTFLAG;
+
+        fn read(&self, record: &Record) {
+            let value = if self.flag.swap(false, Ordering::Relaxed) {
+                ENABLE | COUNTFLAG
+            } else {
+                ENABLE
+            };
+            record.value.store(value, Ordering::Release);
+        }
+
+        fn peek(&self, record: &Record) -> Option<u32> {
+            Some(record.value.load(Ordering::Acquire)).filter(|&value| value != 0)
+        }
+
+        fn preempt(&self) {
+            let step = self.steps.get();
+            self.steps.set(step + 1);
+            let action = self
+                .preempt
+                .borrow()
+                .iter()
+                .flatten()
+                .find(|(at, _)| *at == step)
+                .map(|(_, action)| *action);
+            match action {
+                Some(Action::Wrap) => self.wrap(),
+                Some(Action::Check) => self.check(),
+                Some(Action::WrapCheck) => {
+                    self.wrap();
+                    self.check();
+                }
+                None => {}
+            }
+        }
+    }
+
+    impl FakeCtrl {
+        fn new() -> Self {
+            Self {
+                latch: CountFlagLatch::new(),
+                flag: AtomicBool::new(false),
+                wraps: Cell::new(0),
+                handled: Cell::new(0),
+                steps: Cell::new(0),
+                preempt: RefCell::new([None; 2]),
+            }
+        }
+
+        /// The counter wraps at most once per period, so an overflow is always handled before the next one.
+        fn wrap(&self) {
+            if self.wraps.get() > self.handled.get() {
+                return;
+            }
+            self.flag.store(true, Ordering::Relaxed);
+            self.wraps.set(self.wraps.get() + 1);
+        }
+
+        /// Handle a pending overflow like `UptimeDrv` does.
+        fn check(&self) {
+            let wraps = self.wraps.get();
+            if self.latch.is_pending(self) {
+                self.handled.set(self.handled.get() + 1);
+                self.latch.clear();
+            }
+            // Any wrap before the check must be handled when it returns.
+            assert!(self.handled.get() >= wraps);
+        }
+    }
+
+    #[test]
+    fn latch() {
+        let ctrl = FakeCtrl::new();
+        let latch = &ctrl.latch;
+        assert!(!latch.is_pending(&ctrl));
+        ctrl.wrap();
+        assert!(latch.is_pending(&ctrl));
+        // The flag is cleared in the register, but stays latched.
+        assert!(!ctrl.flag.load(Ordering::Relaxed));
+        assert!(latch.is_pending(&ctrl));
+        latch.clear();
+        assert!(!latch.is_pending(&ctrl));
+    }
+
+    #[test]
+    fn wraps_are_handled_once_when_preempted() {
+        // Preempt any two steps, either by wrapping, checking, or both.
+        let actions = [Action::Wrap, Action::Check, Action::WrapCheck];
+        for pending in [false, true] {
+            for first in 0..16 {
+                for second in first + 1..16 {
+                    for first_action in actions {
+                        for second_action in actions {
+                            let ctrl = FakeCtrl::new();
+                            if pending {
+                                ctrl.wrap();
+                            }
+                            ctrl.preempt.replace([
+                                Some((first, first_action)),
+                                Some((second, second_action)),
+                            ]);
+                            ctrl.check();
+                            ctrl.preempt.replace([None; 2]);
+                            ctrl.check();
+                            assert_eq!(ctrl.wraps.get(), ctrl.handled.get());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
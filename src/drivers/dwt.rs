@@ -0,0 +1,139 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{AlarmCounter, Tick, UptimeCounter, UptimeOverflow};
+use alloc::sync::Arc;
+use drone_core::{fib, thr::prelude::*};
+use drone_cortexm::{
+    map::reg::{dwt, scb},
+    reg::prelude::*,
+};
+
+pub struct Adapter;
+
+/// The most significant bit of the cycle counter.
+const MSB: u32 = 0x8000_0000;
+
+/// A cortex DWT cycle counter uptime driver.
+///
+/// The cycle counter only counts while the core is clocked, so it stops in WFI/WFE
+/// and the uptime does not advance while the processor sleeps.
+/// Use it only where the core never sleeps, or where losing the idle time is acceptable.
+///
+/// The cycle counter does not have an overflow interrupt.
+/// Instead, a periodic interrupt that fires at least once per half cycle counter period,
+/// i.e. every 2^31 cycles (about 12.7s at 168MHz), is used both to detect the overflow and to call `now()` for it.
+/// An overflow is missed if the interrupt is delayed beyond that.
+/// The same interrupt must be used as the timer interrupt for the `UptimeDrv`.
+pub struct DwtUptimeDrv {
+    /// The cycle counter.
+    pub counter: DwtCounterDrv,
+    /// The overflow control.
+    pub overflow: DwtOverflowDrv,
+}
+
+pub struct DwtCounterDrv(dwt::Cyccnt<Crt>);
+
+/// An alarm counter that reads the counter of another timer, but spins on the cycle counter.
+///
+/// This gives `Alarm::burn_nanos()` cycle resolution for an `AlarmDrv` backed by that timer.
+pub struct DwtSpin<Cnt> {
+    counter: Cnt,
+    cyccnt: dwt::Cyccnt<Crt>,
+}
+
+pub struct DwtOverflowDrv {
+    cyccnt: dwt::Cyccnt<Crt>,
+    /// Whether the counter has been observed in the upper half of its range since the last overflow.
+    armed: Arc<AtomicBool>,
+}
+
+impl DwtUptimeDrv {
+    /// Start the cycle counter, and detect its overflows from the `periodic_int` interrupt.
+    pub fn start_new<Int: ThrToken>(
+        scb_demcr: scb::Demcr<Srt>,
+        dwt_ctrl: dwt::Ctrl<Srt>,
+        dwt_cyccnt: dwt::Cyccnt<Srt>,
+        periodic_int: Int,
+    ) -> Self {
+        // Enable the trace and debug blocks, and start the counter.
+        scb_demcr.modify(|r| r.set_trcena());
+        dwt_cyccnt.store(|r| r.write_cyccnt(0));
+        dwt_ctrl.modify(|r| r.set_cyccntena());
+
+        let cyccnt = dwt_cyccnt.into_copy();
+        let armed = Arc::new(AtomicBool::new(false));
+        let upper = armed.clone();
+        periodic_int.add_fn(move || {
+            if cyccnt.load_bits() as u32 & MSB != 0 {
+                upper.store(true, Ordering::Release);
+            }
+            fib::Yielded::<(), ()>(())
+        });
+
+        Self {
+            counter: DwtCounterDrv(cyccnt),
+            overflow: DwtOverflowDrv { cyccnt, armed },
+        }
+    }
+}
+
+impl<Cnt> DwtSpin<Cnt> {
+    /// Create a new alarm counter that reads `counter`, and spins on the running cycle counter of `dwt`.
+    pub fn new(counter: Cnt, dwt: &DwtCounterDrv) -> Self {
+        Self {
+            counter,
+            cyccnt: dwt.0,
+        }
+    }
+}
+
+/// Spin until the cycle counter has advanced by `cycles`.
+fn spin_cycles(cyccnt: &dwt::Cyccnt<Crt>, cycles: u32) {
+    let start = cyccnt.load_bits() as u32;
+    while (cyccnt.load_bits() as u32).wrapping_sub(start) < cycles {}
+}
+
+impl<T: Tick> UptimeCounter<T, Adapter> for DwtCounterDrv {
+    fn value(&self) -> u32 {
+        self.0.load_bits() as u32
+    }
+}
+
+impl UptimeOverflow<Adapter> for DwtOverflowDrv {
+    const MAX: u32 = 0xFFFF_FFFF;
+
+    fn overflow_int_enable(&self) {
+        // The overflow is detected by the periodic interrupt.
+    }
+
+    fn is_pending_overflow(&self) -> bool {
+        // The counter has wrapped if it was in the upper half and is now in the lower half.
+        self.armed.load(Ordering::Acquire) && self.cyccnt.load_bits() as u32 & MSB == 0
+    }
+
+    fn clear_pending_overflow(&self) {
+        self.armed.store(false, Ordering::Release);
+    }
+}
+
+impl<T: Tick> AlarmCounter<T, Adapter> for DwtCounterDrv {
+    fn value(&self) -> u32 {
+        self.0.load_bits() as u32
+    }
+
+    #[inline]
+    fn spin(&self, cycles: u32) {
+        spin_cycles(&self.0, cycles);
+    }
+}
+
+impl<T: Tick, A, Cnt: AlarmCounter<T, A>> AlarmCounter<T, A> for DwtSpin<Cnt> {
+    fn value(&self) -> u32 {
+        self.counter.value()
+    }
+
+    #[inline]
+    fn spin(&self, cycles: u32) {
+        spin_cycles(&self.cyccnt, cycles);
+    }
+}
@@ -0,0 +1,67 @@
+#[cfg(not(feature = "std"))]
+use core::sync::atomic::{compiler_fence, Ordering};
+
+/// Disables all interrupts
+#[inline]
+pub fn disable() {
+    #[cfg(feature = "std")]
+    unimplemented!();
+    #[cfg(not(feature = "std"))]
+    {
+        unsafe { asm!("cpsid i") };
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+/// Enables all (not masked) interrupts
+///
+/// # Safety
+///
+/// - Do not call this function inside a critical section.
+#[inline]
+pub unsafe fn enable() {
+    #[cfg(feature = "std")]
+    unimplemented!();
+    #[cfg(not(feature = "std"))]
+    {
+        compiler_fence(Ordering::SeqCst);
+        asm!("cpsie i");
+    }
+}
+
+#[inline]
+fn primask() -> u32 {
+    #[cfg(feature = "std")]
+    unimplemented!();
+    #[cfg(not(feature = "std"))]
+    {
+        let r: usize;
+        unsafe { asm!("mrs {}, PRIMASK", out(reg) r) };
+        r as u32
+    }
+}
+
+/// Critical section token.
+pub struct CriticalSection;
+
+/// Execute the closure `f` in an interrupt-free context.
+#[inline]
+pub fn critical<F, R>(f: F) -> R
+where
+    F: FnOnce(&CriticalSection) -> R,
+{
+    let pm = primask();
+
+    // Disable interrupts - they may already be disabled if this is a nested critical section.
+    disable();
+
+    let cs = CriticalSection;
+    let r = f(&cs);
+
+    // Only enable interrupt if interrupts were active when entering.
+    if pm & 1 == 0 {
+        unsafe { enable() }
+    }
+
+    r
+}
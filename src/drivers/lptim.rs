@@ -0,0 +1,123 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{Tick, UptimeCounter, UptimeOverflow};
+use drone_cortexm::reg::prelude::*;
+use drone_stm32_map::reg::lptim1;
+
+pub struct Adapter;
+
+/// The auto-reload value, so that the counter uses its full 16 bit range.
+const ARR: u32 = 0xFFFF;
+
+/// A low power timer (LPTIM1) uptime driver, which keeps counting in Stop mode.
+///
+/// The timer must be clocked from the LSE, i.e. RCC_DCKCFGR2.LPTIM1SEL must select the LSE
+/// and the peripheral clock must be enabled, before the driver is started.
+/// Use it as the low power uptime of a `LowPowerUptime`.
+///
+/// The overflow interrupt fires when the counter reaches ARR, one tick before it wraps.
+/// The wrap itself is counted by the next `now()`, or by the interrupt a period later at the latest.
+pub struct LptimUptimeDrv {
+    /// The timer counter.
+    pub counter: LptimCounterDrv,
+    /// The overflow control.
+    pub overflow: LptimOverflowDrv,
+}
+
+pub struct LptimCounterDrv(lptim1::Cnt<Crt>);
+
+pub struct LptimOverflowDrv {
+    lptim_cnt: lptim1::Cnt<Crt>,
+    lptim_isr: lptim1::Isr<Crt>,
+    lptim_icr: lptim1::Icr<Crt>,
+    /// Whether the autoreload match flag is cleared while the counter is at ARR, and the counter is yet to wrap.
+    armed: AtomicBool,
+}
+
+impl LptimUptimeDrv {
+    pub fn start_new(
+        lptim_cr: lptim1::Cr<Srt>,
+        lptim_ier: lptim1::Ier<Srt>,
+        lptim_isr: lptim1::Isr<Srt>,
+        lptim_icr: lptim1::Icr<Srt>,
+        lptim_arr: lptim1::Arr<Srt>,
+        lptim_cnt: lptim1::Cnt<Srt>,
+    ) -> Self {
+        // The interrupts can only be enabled while the timer is disabled.
+        lptim_ier.store(|r| r.set_arrmie());
+        lptim_cr.store(|r| r.set_enable());
+
+        // Configure reload value, which can only be written while the timer is enabled.
+        lptim_arr.store(|r| r.write_arr(ARR));
+        while !lptim_isr.arrok.read_bit() {}
+        lptim_icr.store(|r| r.set_arrokcf());
+
+        // Start the counter in continuous mode.
+        lptim_cr.store(|r| r.set_enable().set_cntstrt());
+
+        let lptim_cnt = lptim_cnt.into_copy();
+        Self {
+            counter: LptimCounterDrv(lptim_cnt),
+            overflow: LptimOverflowDrv {
+                lptim_cnt,
+                lptim_isr: lptim_isr.into_copy(),
+                lptim_icr: lptim_icr.into_copy(),
+                armed: AtomicBool::new(false),
+            },
+        }
+    }
+}
+
+/// Read the counter, which is clocked asynchronously to the bus.
+fn read_cnt(lptim_cnt: &lptim1::Cnt<Crt>) -> u32 {
+    // A read is only reliable if two consecutive reads are equal.
+    let mut value = lptim_cnt.load_bits() as u32;
+    loop {
+        let again = lptim_cnt.load_bits() as u32;
+        if again == value {
+            break value;
+        }
+        value = again;
+    }
+}
+
+impl<T: Tick> UptimeCounter<T, Adapter> for LptimCounterDrv {
+    fn value(&self) -> u32 {
+        read_cnt(&self.0)
+    }
+}
+
+impl UptimeOverflow<Adapter> for LptimOverflowDrv {
+    const MAX: u32 = ARR;
+
+    fn overflow_int_enable(&self) {
+        // The interrupt is enabled when the driver is started, as it cannot be enabled while the timer runs.
+    }
+
+    fn is_pending_overflow(&self) -> bool {
+        // The autoreload match is flagged when the counter reaches ARR, one tick before it wraps,
+        // so the overflow is not pending until the counter has left ARR.
+        if self.armed.load(Ordering::Acquire) {
+            // The flag is set again if the counter has wrapped and reached ARR since it was cleared.
+            self.lptim_isr.arrm.read_bit() || read_cnt(&self.lptim_cnt) != ARR
+        } else if self.lptim_isr.arrm.read_bit() {
+            if read_cnt(&self.lptim_cnt) != ARR {
+                return true;
+            }
+            // Clear the flag so that the interrupt does not fire repeatedly until the counter wraps,
+            // the wrap is then detected from the counter, or by the next autoreload match at the latest.
+            self.lptim_icr.store(|r| r.set_arrmcf());
+            self.armed.store(true, Ordering::Release);
+            read_cnt(&self.lptim_cnt) != ARR
+        } else {
+            false
+        }
+    }
+
+    fn clear_pending_overflow(&self) {
+        if !self.armed.swap(false, Ordering::AcqRel) {
+            self.lptim_icr.store(|r| r.set_arrmcf());
+        }
+        // Otherwise the flag is already cleared, or is set for the next wrap, which is then still pending.
+    }
+}
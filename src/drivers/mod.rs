@@ -1,14 +1,35 @@
+#[cfg(feature = "systick")]
+mod interrupt;
+
 #[cfg(feature = "systick")]
 mod systick;
 
+#[cfg(feature = "dwt")]
+mod dwt;
+
 #[cfg(feature = "stm32f4")]
 mod stm32f4;
 
+#[cfg(feature = "lptim")]
+mod lptim;
+
+#[cfg(feature = "systick-experimental")]
+mod count_flag;
+
 #[cfg(feature = "systick-experimental")]
 mod systick_experimental;
 
+#[cfg(feature = "dwt")]
+pub use self::dwt::{Adapter as DwtAdapter, DwtCounterDrv, DwtOverflowDrv, DwtSpin, DwtUptimeDrv};
+
 #[cfg(feature = "systick")]
 pub use self::systick::*;
 
+#[cfg(feature = "stm32f4")]
+pub use self::stm32f4::{tim_cascade, TimCapture, TimCascade, TimWidth};
+
+#[cfg(feature = "lptim")]
+pub use self::lptim::{LptimCounterDrv, LptimOverflowDrv, LptimUptimeDrv};
+
 #[cfg(feature = "systick-experimental")]
-pub use self::systick_experimental::SysTickDrv;
+pub use self::systick_experimental::{SysTickDrv, SysTickDrvCounter, SysTickDrvOverflow};
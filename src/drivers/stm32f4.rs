@@ -1,25 +1,136 @@
-use crate::{AlarmCounter, AlarmTimer, Tick, UptimeCounter, UptimeOverflow};
+use crate::{AlarmCounter, AlarmTimer, CaptureChannel, Edge, Tick, UptimeCounter, UptimeOverflow};
 use async_trait::async_trait;
 use drone_cortexm::processor::spin;
-use drone_stm32_map::periph::tim::general::GeneralTimMap;
+use drone_stm32_map::periph::tim::general::{GeneralTimMap, GeneralTimPeriph, Tim11, Tim5, Tim9};
+#[cfg(not(stm32_mcu = "stm32f410"))]
+use drone_stm32_map::periph::tim::general::{Tim10, Tim2, Tim3, Tim4};
+#[cfg(not(any(
+    stm32_mcu = "stm32f401",
+    stm32_mcu = "stm32f410",
+    stm32_mcu = "stm32f411"
+)))]
+use drone_stm32_map::periph::tim::general::{Tim12, Tim13, Tim14};
 use drone_stm32f4_hal::{
     tim::{
         DirCountUp, GeneralTimCh, GeneralTimChDrv, GeneralTimCntDrv, GeneralTimOvfDrv,
-        OutputCompareMode, TimerCompareCh, TimerCounter, TimerOverflow,
+        InputCaptureMode, OutputCompareMode, TimerCaptureCh, TimerCompareCh, TimerCounter,
+        TimerOverflow,
     },
     IntToken,
 };
 
 pub struct Adapter;
 
+/// The counter width of a general purpose timer, 16 bits unless overridden.
+///
+/// The auto-reload value of the timer must be configured to `MAX`.
+pub trait TimWidth {
+    /// The maximum counter value.
+    const MAX: u32 = 0xFFFF;
+}
+
+#[cfg(not(stm32_mcu = "stm32f410"))]
+impl TimWidth for Tim2 {
+    const MAX: u32 = 0xFFFF_FFFF;
+}
+
+#[cfg(not(stm32_mcu = "stm32f410"))]
+impl TimWidth for Tim3 {}
+
+#[cfg(not(stm32_mcu = "stm32f410"))]
+impl TimWidth for Tim4 {}
+
+impl TimWidth for Tim5 {
+    const MAX: u32 = 0xFFFF_FFFF;
+}
+
+impl TimWidth for Tim9 {}
+
+#[cfg(not(stm32_mcu = "stm32f410"))]
+impl TimWidth for Tim10 {}
+
+impl TimWidth for Tim11 {}
+
+#[cfg(not(any(
+    stm32_mcu = "stm32f401",
+    stm32_mcu = "stm32f410",
+    stm32_mcu = "stm32f411"
+)))]
+impl TimWidth for Tim12 {}
+
+#[cfg(not(any(
+    stm32_mcu = "stm32f401",
+    stm32_mcu = "stm32f410",
+    stm32_mcu = "stm32f411"
+)))]
+impl TimWidth for Tim13 {}
+
+#[cfg(not(any(
+    stm32_mcu = "stm32f401",
+    stm32_mcu = "stm32f410",
+    stm32_mcu = "stm32f411"
+)))]
+impl TimWidth for Tim14 {}
+
+/// A 16-bit general purpose timer that can be cascaded into `Slave`, so that `Slave` counts its wraps.
+pub trait TimCascade<Slave: GeneralTimMap>: GeneralTimMap {
+    /// The internal trigger input (ITRx) of `Slave` that the timer is connected to.
+    const ITR: u32;
+}
+
+#[cfg(not(stm32_mcu = "stm32f410"))]
+impl TimCascade<Tim4> for Tim3 {
+    const ITR: u32 = 2;
+}
+
+#[cfg(not(stm32_mcu = "stm32f410"))]
+impl TimCascade<Tim3> for Tim4 {
+    const ITR: u32 = 3;
+}
+
+/// Chain the `master` and `slave` timers, so that they can be read as one 32-bit counter with `CascadeCounter`.
+///
+/// The master outputs its update event on TRGO, and the slave is clocked by that trigger in external clock mode 1.
+/// Must be called before the timers are started,
+/// and both timers must have their auto-reload value configured to 0xFFFF.
+pub fn tim_cascade<Master: TimCascade<Slave>, Slave: GeneralTimMap>(
+    master: &GeneralTimPeriph<Master>,
+    slave: &GeneralTimPeriph<Slave>,
+) {
+    // MMS = 0b010: the update event is the trigger output.
+    master.tim_cr2.modify_reg(|r, v| r.mms().write(v, 0b010));
+    // SMS = 0b111: count the rising edges of the trigger selected by TS.
+    slave.tim_smcr.modify_reg(|r, v| {
+        r.ts().write(v, Master::ITR);
+        r.sms().write(v, 0b111);
+    });
+}
+
+/// An input capture channel of a general purpose timer.
+///
+/// The channel must be configured to capture on `edge` only.
+pub struct TimCapture<Drv> {
+    drv: Drv,
+    edge: Edge,
+}
+
+impl<Drv> TimCapture<Drv> {
+    /// Create a new capture channel from the channel driver `drv` that captures on `edge`.
+    pub fn new(drv: Drv, edge: Edge) -> Self {
+        Self { drv, edge }
+    }
+}
+
 impl<Tim: GeneralTimMap, T: Tick> UptimeCounter<T, Adapter> for GeneralTimCntDrv<Tim, DirCountUp> {
     fn value(&self) -> u32 {
         TimerCounter::value(self)
     }
 }
 
-impl<Tim: GeneralTimMap, Int: IntToken> UptimeOverflow<Adapter> for GeneralTimOvfDrv<Tim, Int> {
-    const MAX: u32 = 0xFFFF;
+impl<Tim: GeneralTimMap + TimWidth, Int: IntToken> UptimeOverflow<Adapter>
+    for GeneralTimOvfDrv<Tim, Int>
+{
+    const MAX: u32 = <Tim as TimWidth>::MAX;
 
     fn overflow_int_enable(&self) {
         TimerOverflow::int_enable(self);
@@ -46,14 +157,39 @@ impl<Tim: GeneralTimMap, T: Tick> AlarmCounter<T, Adapter> for GeneralTimCntDrv<
 }
 
 #[async_trait]
-impl<Tim: GeneralTimMap, Int: IntToken, Ch: GeneralTimCh<Tim>, T: Tick> AlarmTimer<T, Adapter>
-    for GeneralTimChDrv<Tim, Int, Ch, OutputCompareMode>
+impl<Tim: GeneralTimMap + TimWidth, Int: IntToken, Ch: GeneralTimCh<Tim>, T: Tick>
+    AlarmTimer<T, Adapter> for GeneralTimChDrv<Tim, Int, Ch, OutputCompareMode>
 {
-    const MAX: u32 = 0xFFFF;
+    const MAX: u32 = <Tim as TimWidth>::MAX;
 
     async fn next(&mut self, compare: u32, soon: bool) {
-        assert!(compare <= 0xFFFF);
+        assert!(compare <= <Tim as TimWidth>::MAX);
 
         TimerCompareCh::next(self, compare, soon).await;
     }
 }
+
+impl<Tim: GeneralTimMap, Int: IntToken, Ch: GeneralTimCh<Tim>> CaptureChannel<Adapter>
+    for TimCapture<GeneralTimChDrv<Tim, Int, Ch, InputCaptureMode>>
+{
+    fn capture_int_enable(&self) {
+        TimerCaptureCh::int_enable(&self.drv);
+    }
+
+    fn take_capture(&self) -> Option<(u32, Edge)> {
+        if TimerCaptureCh::is_pending(&self.drv) {
+            // Reading the captured value clears the pending flag.
+            Some((TimerCaptureCh::value(&self.drv), self.edge))
+        } else {
+            None
+        }
+    }
+
+    fn take_overcapture(&self) -> bool {
+        let overcapture = TimerCaptureCh::is_overcapture(&self.drv);
+        if overcapture {
+            TimerCaptureCh::clear_overcapture(&self.drv);
+        }
+        overcapture
+    }
+}
@@ -0,0 +1,275 @@
+/// The reload value when free-running.
+pub(crate) const RELOAD: u32 = 0xFFFFFF;
+/// The free-running period.
+pub(crate) const PERIOD: u64 = RELOAD as u64 + 1;
+/// The minimum reload value after a restart, so that the reload is observed long before the counter wraps.
+const MIN_RELOAD: u32 = 64;
+
+/// The SysTick registers that the clock state follows.
+pub(crate) trait SysTickRegs {
+    /// Read the COUNTFLAG, which clears it.
+    fn countflag(&self) -> bool;
+
+    /// Read the current counter value.
+    fn val(&self) -> u32;
+
+    /// Clear the counter so that it reloads `reload` on the next tick, and is free-running after that.
+    fn restart(&self, reload: u32);
+}
+
+/// The time of a free-running SysTick that can be restarted to wrap at a deadline.
+pub(crate) struct State {
+    /// The time when the counter last reached 0.
+    boundary: u64,
+    /// The reload value of the current period.
+    reload: u32,
+    /// The deadline of the armed alarm.
+    pub(crate) deadline: Option<u64>,
+}
+
+impl State {
+    pub(crate) const fn new() -> Self {
+        Self {
+            boundary: 0,
+            reload: RELOAD,
+            deadline: None,
+        }
+    }
+
+    /// Get the current time in ticks, accounting for a wrap of the counter.
+    pub(crate) fn ticks(&mut self, regs: &impl SysTickRegs) -> u64 {
+        // Reading the COUNTFLAG clears it, so it must be accounted for in the state right away.
+        if regs.countflag() {
+            self.wrap();
+        }
+        let mut val = regs.val();
+        if regs.countflag() {
+            // The counter wrapped after the flag was read, and maybe before the value was read.
+            self.wrap();
+            val = regs.val();
+        }
+        self.at(val)
+    }
+
+    /// Restart the counter, so that it wraps at `deadline` if it falls within the current period.
+    /// Returns false if the `deadline` is too soon to restart for.
+    pub(crate) fn restart(&mut self, regs: &impl SysTickRegs, now: u64, deadline: u64) -> bool {
+        if deadline >= self.end() {
+            // The deadline is handled when the counter wraps.
+            return true;
+        }
+        if deadline < now + MIN_RELOAD as u64 + 1 {
+            return false;
+        }
+
+        let reload = (deadline - now - 1) as u32;
+        regs.restart(reload);
+        self.boundary = now;
+        self.reload = reload;
+        true
+    }
+
+    fn wrap(&mut self) {
+        self.boundary += self.reload as u64 + 1;
+        self.reload = RELOAD;
+    }
+
+    /// Get the time when the counter reaches 0 again.
+    fn end(&self) -> u64 {
+        self.boundary + self.reload as u64 + 1
+    }
+
+    /// Get the time for the counter `val` in the current period.
+    fn at(&self, val: u32) -> u64 {
+        if val == 0 {
+            self.boundary
+        } else {
+            self.boundary + (self.reload + 1 - val) as u64
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use core::cell::{Cell, RefCell};
+
+    use alloc::collections::VecDeque;
+
+    use super::*;
+
+    /// A SysTick that counts down from `load` to 0, as if started at time 0.
+    struct FakeSysTick {
+        time: Cell<u64>,
+        load: Cell<u32>,
+        val: Cell<u32>,
+        flag: Cell<bool>,
+        /// The ticks that pass before each register access.
+        steps: RefCell<VecDeque<u64>>,
+        /// The time when the value was last read.
+        read_at: Cell<u64>,
+    }
+
+    impl FakeSysTick {
+        fn new() -> Self {
+            Self {
+                time: Cell::new(0),
+                load: Cell::new(RELOAD),
+                val: Cell::new(0),
+                flag: Cell::new(false),
+                steps: RefCell::new(VecDeque::new()),
+                read_at: Cell::new(0),
+            }
+        }
+
+        fn advance(&self, mut ticks: u64) {
+            self.time.set(self.time.get() + ticks);
+            while ticks > 0 {
+                if self.val.get() == 0 {
+                    self.val.set(self.load.get());
+                    ticks -= 1;
+                    continue;
+                }
+                let step = ticks.min(self.val.get() as u64);
+                self.val.set(self.val.get() - step as u32);
+                ticks -= step;
+                if self.val.get() == 0 {
+                    self.flag.set(true);
+                }
+            }
+        }
+
+        fn access(&self) {
+            if let Some(step) = self.steps.borrow_mut().pop_front() {
+                self.advance(step);
+            }
+        }
+    }
+
+    impl SysTickRegs for FakeSysTick {
+        fn countflag(&self) -> bool {
+            self.access();
+            self.flag.replace(false)
+        }
+
+        fn val(&self) -> u32 {
+            self.access();
+            self.read_at.set(self.time.get());
+            self.val.get()
+        }
+
+        fn restart(&self, reload: u32) {
+            self.load.set(reload);
+            self.val.set(0);
+            self.flag.set(false);
+            // Spin until the reload is observed.
+            self.advance(1);
+            self.load.set(RELOAD);
+        }
+    }
+
+    #[test]
+    fn ticks() {
+        let systick = FakeSysTick::new();
+        let mut state = State::new();
+        // The time is read at least once per period.
+        for ticks in [
+            0,
+            1,
+            PERIOD - 1,
+            PERIOD,
+            PERIOD + 1,
+            2 * PERIOD,
+            3 * PERIOD - 1,
+        ] {
+            systick.advance(ticks - systick.time.get());
+            assert_eq!(ticks, state.ticks(&systick));
+        }
+    }
+
+    #[test]
+    fn wrap_during_ticks() {
+        // The counter wraps before, between, or after the flag and the value are read.
+        for access in 0..4 {
+            for before in [1, 2] {
+                let systick = FakeSysTick::new();
+                let mut state = State::new();
+                systick.advance(PERIOD - before);
+                let mut steps = VecDeque::from(vec![0; 4]);
+                steps[access] = before;
+                systick.steps.replace(steps);
+
+                let now = state.ticks(&systick);
+                assert_eq!(systick.read_at.get(), now);
+                systick.steps.replace(VecDeque::new());
+                assert_eq!(systick.time.get(), state.ticks(&systick));
+            }
+        }
+    }
+
+    #[test]
+    fn restart_within_period() {
+        let systick = FakeSysTick::new();
+        let mut state = State::new();
+        systick.advance(PERIOD + 1000);
+        let now = state.ticks(&systick);
+        let deadline = now + 500;
+
+        assert!(state.restart(&systick, now, deadline));
+        assert_eq!(now + 1, state.ticks(&systick));
+
+        // The counter wraps exactly at the deadline.
+        systick.advance(deadline - 1 - systick.time.get());
+        assert_eq!(deadline - 1, state.ticks(&systick));
+        assert!(!systick.flag.get());
+        systick.advance(1);
+        assert!(systick.flag.get());
+        assert_eq!(deadline, state.ticks(&systick));
+
+        // The following period is free-running again.
+        systick.advance(PERIOD);
+        assert_eq!(deadline + PERIOD, state.ticks(&systick));
+    }
+
+    #[test]
+    fn restart_too_soon() {
+        let systick = FakeSysTick::new();
+        let mut state = State::new();
+        systick.advance(1000);
+        let now = state.ticks(&systick);
+
+        assert!(!state.restart(&systick, now, now + MIN_RELOAD as u64));
+        assert_eq!(RELOAD, systick.load.get());
+        assert_eq!(now, state.ticks(&systick));
+    }
+
+    #[test]
+    fn restart_beyond_period() {
+        let systick = FakeSysTick::new();
+        let mut state = State::new();
+        systick.advance(1000);
+        let now = state.ticks(&systick);
+        let deadline = now + 2 * PERIOD + 500;
+        state.deadline = Some(deadline);
+
+        // The deadline is not in the current period, so the counter keeps running.
+        assert!(state.restart(&systick, now, deadline));
+        assert_eq!(now, state.ticks(&systick));
+
+        // Handle the wraps like the SysTick interrupt does, until the counter is restarted for the deadline.
+        loop {
+            let val = match systick.val.get() {
+                0 => systick.load.get() + 1,
+                val => val,
+            };
+            systick.advance(val as u64);
+            assert!(systick.flag.get());
+            let now = state.ticks(&systick);
+            assert_eq!(systick.time.get(), now);
+            if now == deadline {
+                break;
+            }
+            assert!(now < deadline);
+            assert!(state.restart(&systick, now, deadline));
+        }
+    }
+}
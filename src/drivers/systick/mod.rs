@@ -1,7 +1,11 @@
 mod alarm;
+mod clock;
 mod diverged;
 mod uptime;
+mod uptime_alarm;
 
 pub struct Adapter;
 
-pub use self::{alarm::SysTickAlarmDrv, uptime::SysTickUptimeDrv};
+pub use self::{
+    alarm::SysTickAlarmDrv, uptime::SysTickUptimeDrv, uptime_alarm::SysTickUptimeAlarmDrv,
+};
@@ -0,0 +1,193 @@
+use alloc::sync::Arc;
+
+use crate::{drivers::interrupt, AlarmCounter, AlarmTimer, Tick, TimeSpan, Uptime};
+use async_trait::async_trait;
+use drone_core::{fib, sync::Mutex, thr::prelude::*};
+use drone_cortexm::{
+    map::{periph::sys_tick::SysTickPeriph, reg::stk},
+    processor::spin,
+    reg::prelude::*,
+};
+
+use super::{
+    clock::{State, SysTickRegs, PERIOD, RELOAD},
+    Adapter,
+};
+
+/// A cortex SysTick driver that provides both an uptime and an alarm.
+///
+/// SysTick is free-running with the full 24 bit reload, and the time is accumulated on each wrap.
+/// The alarm is emulated on top of it: when a deadline falls within the current period,
+/// the counter is restarted with a reload such that it wraps exactly at the deadline,
+/// and the time is carried over to the new period.
+/// Each restart loses the few cycles that it takes to reprogram the counter.
+pub struct SysTickUptimeAlarmDrv<Int: ThrToken> {
+    /// The uptime.
+    pub uptime: Arc<SysTickClock>,
+    /// The alarm counter.
+    pub counter: SysTickClockCounterDrv,
+    /// The alarm timer control.
+    pub timer: SysTickClockTimerDrv<Int>,
+}
+
+/// The free-running SysTick time shared by the uptime and the alarm.
+pub struct SysTickClock {
+    stk_ctrl: stk::Ctrl<Crt>,
+    stk_load: stk::Load<Crt>,
+    stk_val: stk::Val<Crt>,
+    /// Only accessed with interrupts disabled.
+    state: Mutex<State>,
+}
+
+pub struct SysTickClockCounterDrv(Arc<SysTickClock>);
+pub struct SysTickClockTimerDrv<Int: ThrToken>(Arc<SysTickClock>, Int);
+
+impl<Int: ThrToken> SysTickUptimeAlarmDrv<Int> {
+    pub fn start_new(systick: SysTickPeriph, systick_int: Int) -> Self {
+        // Configure reload value.
+        systick.stk_load.store(|r| r.write_reload(RELOAD));
+
+        let clock = Arc::new(SysTickClock {
+            stk_ctrl: systick.stk_ctrl.into_copy(),
+            stk_load: systick.stk_load.into_copy(),
+            stk_val: systick.stk_val.into_copy(),
+            state: Mutex::new(State::new()),
+        });
+
+        // Account for each wrap, and restart the counter if the deadline falls within the new period.
+        let wrapped = clock.clone();
+        systick_int.add_fn(move || {
+            wrapped.critical(|state| {
+                let now = state.ticks(&*wrapped);
+                if let Some(deadline) = state.deadline {
+                    state.restart(&*wrapped, now, deadline);
+                }
+            });
+            fib::Yielded::<(), ()>(())
+        });
+
+        // Start the counter in a multi-shot way
+        // Counting down to 0 triggers the SysTick interrupt
+        systick.stk_ctrl.store(|r| r.set_tickint().set_enable());
+
+        Self {
+            uptime: clock.clone(),
+            counter: SysTickClockCounterDrv(clock.clone()),
+            timer: SysTickClockTimerDrv(clock, systick_int),
+        }
+    }
+}
+
+impl SysTickClock {
+    fn critical<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        interrupt::critical(|_| {
+            // The state is never locked when interrupts are enabled.
+            let mut state = self.state.try_lock().unwrap();
+            f(&mut state)
+        })
+    }
+
+    fn now_ticks(&self) -> u64 {
+        self.critical(|state| state.ticks(self))
+    }
+
+    /// Arm the alarm for `deadline`.
+    /// Returns false if the `deadline` is too soon to wait for the interrupt.
+    fn arm(&self, deadline: u64) -> bool {
+        self.critical(|state| {
+            let now = state.ticks(self);
+            state.deadline = Some(deadline);
+            state.restart(self, now, deadline)
+        })
+    }
+
+    fn disarm(&self) {
+        self.critical(|state| state.deadline = None);
+    }
+}
+
+impl SysTickRegs for SysTickClock {
+    fn countflag(&self) -> bool {
+        self.stk_ctrl.countflag.read_bit()
+    }
+
+    fn val(&self) -> u32 {
+        self.stk_val.load_bits() as u32
+    }
+
+    fn restart(&self, reload: u32) {
+        self.stk_load.store(|r| r.write_reload(reload));
+        // Writing the value clears the counter, which reloads on the next tick.
+        self.stk_val.store(|r| r.write_current(0));
+        while self.stk_val.load_bits() == 0 {}
+        // The following periods are free-running again.
+        self.stk_load.store(|r| r.write_reload(RELOAD));
+    }
+}
+
+impl<T: Tick> Uptime<T> for SysTickClock {
+    fn counter(&self) -> u32 {
+        self.now_ticks() as u32
+    }
+
+    fn now(&self) -> TimeSpan<T> {
+        TimeSpan::from_ticks(self.now_ticks() as i64)
+    }
+
+    fn at(&self, counter: u32) -> TimeSpan<T> {
+        let now = self.now_ticks();
+        TimeSpan::from_ticks((now - (now as u32).wrapping_sub(counter) as u64) as i64)
+    }
+}
+
+impl<T: Tick> AlarmCounter<T, Adapter> for SysTickClockCounterDrv {
+    fn value(&self) -> u32 {
+        self.0.now_ticks() as u32 & RELOAD
+    }
+
+    #[inline]
+    fn spin(&self, cycles: u32) {
+        spin(cycles);
+    }
+}
+
+/// Disarms the alarm when the wait is completed or dropped.
+struct Disarm<'a>(&'a SysTickClock);
+
+impl Drop for Disarm<'_> {
+    fn drop(&mut self) {
+        self.0.disarm();
+    }
+}
+
+#[async_trait]
+impl<Int: ThrToken, T: Tick> AlarmTimer<T, Adapter> for SysTickClockTimerDrv<Int> {
+    const MAX: u32 = RELOAD;
+
+    async fn next(&mut self, compare: u32, soon: bool) {
+        let now = self.0.now_ticks();
+        let ticks = compare.wrapping_sub(now as u32) & RELOAD;
+        if soon && (ticks == 0 || ticks as u64 >= PERIOD / 2) {
+            // The compare value has already passed.
+            return;
+        }
+        let deadline = now + if ticks == 0 { PERIOD } else { ticks as u64 };
+
+        let clock = self.0.clone();
+        let future = self.1.add_future(fib::new_fn(move || {
+            if clock.now_ticks() >= deadline {
+                fib::Complete(())
+            } else {
+                fib::Yielded(())
+            }
+        }));
+
+        let _disarm = Disarm(&self.0);
+        if self.0.arm(deadline) {
+            future.await;
+        } else {
+            // The deadline is too soon to restart the counter for.
+            while self.0.now_ticks() < deadline {}
+        }
+    }
+}
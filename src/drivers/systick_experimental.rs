@@ -1,99 +1,162 @@
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::{
+    ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use crate::UptimeAlarm;
-use drone_cortexm::{map::periph::sys_tick::SysTickPeriph, reg::prelude::*};
+use crate::{
+    drivers::count_flag::{ClearOnRead, CountFlagLatch, Record},
+    Tick, UptimeCounter, UptimeOverflow,
+};
+use drone_cortexm::{
+    map::{periph::sys_tick::SysTickPeriph, reg::stk},
+    reg::prelude::*,
+};
 
-pub struct SysTickDrv(SysTickPeriph, AtomicUsize);
+pub struct Adapter;
+
+/// The COUNTFLAG bit in SYST_CSR.
+const COUNTFLAG: u32 = 1 << 16;
+/// The size of an exception frame without and with the floating point context.
+const BASIC_FRAME: usize = 8 * 4;
+const EXTENDED_FRAME: usize = 26 * 4;
+/// The index of the stacked PC in an exception frame.
+const FRAME_PC: usize = 6;
+
+/// The address of the instruction that stores the value read from SYST_CSR, see `SysTickDrvOverflow::read()`.
+static COUNTFLAG_STORE: AtomicUsize = AtomicUsize::new(0);
+
+/// A cortex SysTick uptime driver that detects overflows without disabling interrupts.
+///
+/// Reading the COUNTFLAG clears it, so a thread that preempts another one
+/// right after it has read the flag, but before it has latched it, would miss the overflow.
+/// The shipped `SysTickUptimeDrv` closes that window with a critical section.
+/// This driver instead lets the preempting thread find the value read by the preempted thread
+/// in its exception frame, and latch the flag on its behalf.
+///
+/// Requires CCR.STKALIGN to be set, which is the reset value on Cortex-M3 r2p0 and later.
+pub struct SysTickDrv {
+    /// The timer counter.
+    pub counter: SysTickDrvCounter,
+    /// The overflow control.
+    pub overflow: SysTickDrvOverflow,
+}
+
+pub struct SysTickDrvCounter(stk::Val<Crt>);
+
+pub struct SysTickDrvOverflow {
+    stk_ctrl: stk::Ctrl<Crt>,
+    latch: CountFlagLatch,
+}
 
 impl SysTickDrv {
-    pub fn new(systick: SysTickPeriph) -> Self {
-        Self(systick, AtomicUsize::new(0))
+    pub fn start_new(systick: SysTickPeriph) -> Self {
+        // Configure reload value.
+        systick.stk_load.store(|r| r.write_reload(0xFFFFFF));
+
+        // Start the counter in a multi-shot way
+        systick.stk_ctrl.store(|r| r.set_enable());
+
+        Self {
+            counter: SysTickDrvCounter(systick.stk_val.into_copy()),
+            overflow: SysTickDrvOverflow {
+                stk_ctrl: systick.stk_ctrl.into_copy(),
+                latch: CountFlagLatch::new(),
+            },
+        }
     }
 }
 
-unsafe impl Sync for SysTickDrv {}
+impl<T: Tick> UptimeCounter<T, Adapter> for SysTickDrvCounter {
+    fn value(&self) -> u32 {
+        // SysTick counts down, but the returned counter value must count up.
+        0xFFFFFF - self.0.load_bits() as u32
+    }
+}
 
-impl UptimeAlarm<SysTickDrv> for SysTickDrv {
-    fn start(&self) {
-        // Enable timer
-        self.0.stk_load.store(|r| r.write_reload(0xFF_FF_FF));
+impl UptimeOverflow<Adapter> for SysTickDrvOverflow {
+    const MAX: u32 = 0xFFFFFF; // SysTick is a 24 bit counter.
 
-        self.0.stk_ctrl.store(|r| {
-            r.set_tickint() // Counting down to 0 triggers the SysTick interrupt
-                .set_enable() // Start the counter in a multi-shot way
-        });
+    fn overflow_int_enable(&self) {
+        // Counting down to 0 triggers the SysTick interrupt
+        self.stk_ctrl.modify(|r| r.set_tickint());
     }
 
-    fn counter(&self) -> u32 {
-        // SysTick counts down, but the returned counter value must count up.
-        0xFF_FF_FF - self.0.stk_val.load_bits() as u32
+    fn is_pending_overflow(&self) -> bool {
+        self.latch.is_pending(self)
     }
 
-    fn counter_max() -> u32 {
-        // SysTick is a 24 bit counter.
-        0xFF_FF_FF
+    fn clear_pending_overflow(&self) {
+        self.latch.clear();
     }
+}
+
+impl ClearOnRead for SysTickDrvOverflow {
+    const FLAG: u32 = COUNTFLAG;
 
+    /// Read SYST_CSR into r0 and then store it in `record.value`.
+    ///
+    /// Before reading, the address of the store is published in `COUNTFLAG_STORE`,
+    /// and the stack pointer is published in `record.context`, with the FPCA bit of CONTROL in bit 0.
+    /// If the thread is preempted between the read and the store,
+    /// the read value is in the stacked r0 of the exception frame right below that stack pointer.
     #[inline(never)]
-    fn is_pending_overflow(&self) -> bool {
-        // SysTick is inherently racy as reading the COUNTFLAG that tells whether the timer has overflowed,
-        // _also clears the flag_. That means that any other interrupting thread cannot read flag.
-
-        // Pseudo code - does not compile:
-        const SYSTICK_CTRL: usize = 0xE000E010;
-        const SP_TO_R0_OFFSET: usize = 2;
-        let atomic = &self.1;
-        unsafe { asm!("nop") };
-        // Set register r0 to an impossible SYSTICK_CTRL value.
-        // This register is reserved for the SYSTICK_CTRL,
-        // so that any thread that may interrupt us can go and read its value
-        // as an offset of our stack pointer which we will save in a moment.
-        let mut r0 = 0;
-        let my_sp: usize; // Get this threads stack pointer.
-        unsafe { asm!("mov {}, SP", out(reg) my_sp) };
-        // Store our stack pointer in the atomic. It can contain three different kinds of value:
-        // ==0 - The latched overflow flag is not set and no other thread is currently reading SYSTICK_CTRL.
-        // ==1 - The latched overflow flag is currently set.
-        // >=2 - The stack pointer of the thread that is currently reading SYSTICK_CTRL.
-        let preempted_sp = atomic.compare_and_swap(0, my_sp, Ordering::AcqRel);
-        if preempted_sp >= 2 {
-            // We have preempted at least one thread.
-            // Lets see if the other thread has yet read SYSTICK_CTRL into r0.
-            let preempted_r0 = unsafe {
-                core::ptr::read_volatile(
-                    ((preempted_sp & 0xFFFFFFFE) as *const usize).add(SP_TO_R0_OFFSET),
-                )
-            };
-            if preempted_r0 == 0 {
-                // The preempted thread has not yet read SYSTICK_CTRL - its value is invalid.
-                // Steal the stack pointer atomic from the preempted thread
-                // as other interrupting threads should read our r0,
-                // and not the one we have just found to be invalid.
-                atomic.compare_and_swap(preempted_sp, my_sp, Ordering::Release);
-                r0 = SYSTICK_CTRL;
-            } else {
-                // The preempted thread had SYSTICK_CTRL read to R0 on its stack.
-                // Use that value instead of the just cleared SYSTICK_CTRL.
-                r0 = preempted_r0;
-            }
-        } else {
-            // We have not preempted any other threads.
-            r0 = SYSTICK_CTRL;
+    fn read(&self, record: &Record) {
+        #[cfg(feature = "std")]
+        {
+            let _ = record;
+            unimplemented!();
         }
-
-        if r0 & 0x10000 > 0 {
-            // COUNTFLAG is set in R0
-            // Store the flag instead of our stack pointer, but only do so if we were not interrupted.
-            // If we were, we must not set the latched flag, as the preempting thread may have
-            // cleared the flag already.
-            atomic.compare_and_swap(my_sp, 1, Ordering::Release);
+        #[cfg(not(feature = "std"))]
+        unsafe {
+            asm!(
+                "adr {tmp}, 2f",
+                "str {tmp}, [{store_ptr}]",
+                "mrs {context}, CONTROL",
+                "ands {context}, {context}, #4",
+                "lsrs {context}, {context}, #2",
+                "mov {tmp}, sp",
+                "orrs {context}, {context}, {tmp}",
+                "str {context}, [{context_ptr}]",
+                "ldr r0, [{ctrl}]",
+                "2:",
+                "str r0, [{value_ptr}]",
+                context = out(reg) _,
+                tmp = out(reg) _,
+                store_ptr = in(reg) &COUNTFLAG_STORE as *const _ as *mut usize,
+                context_ptr = in(reg) &record.context as *const _ as *mut usize,
+                ctrl = in(reg) stk::Ctrl::<Crt>::ADDRESS,
+                value_ptr = in(reg) &record.value as *const _ as *mut u32,
+                out("r0") _,
+                options(nostack),
+            );
         }
-
-        // The truth is now latched in the atomic: re-read it.
-        atomic.load(Ordering::Acquire) == 1
     }
 
-    fn clear_pending_overflow(&self) {
-        self.1.store(0, Ordering::Release);
+    fn peek(&self, record: &Record) -> Option<u32> {
+        let value = record.value.load(Ordering::Acquire);
+        if value != 0 {
+            return Some(value);
+        }
+        let context = record.context.load(Ordering::Acquire);
+        if context == 0 {
+            // The stack pointer is not published, so the register is not read yet.
+            return None;
+        }
+        let frame_size = if context & 1 == 0 {
+            BASIC_FRAME
+        } else {
+            EXTENDED_FRAME
+        };
+        // The exception frame is pushed right below the stack pointer, aligned to 8 bytes.
+        let frame = (((context & !1) - frame_size) & !7) as *const u32;
+        unsafe {
+            let store = COUNTFLAG_STORE.load(Ordering::Acquire) as u32;
+            // The preempted thread has read the register, but not stored it yet.
+            if ptr::read_volatile(frame.add(FRAME_PC)) == store {
+                Some(ptr::read_volatile(frame))
+            } else {
+                None
+            }
+        }
     }
 }
@@ -0,0 +1,8 @@
+/// A signal edge.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Edge {
+    /// The signal went from low to high.
+    Rising,
+    /// The signal went from high to low.
+    Falling,
+}
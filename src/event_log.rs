@@ -0,0 +1,207 @@
+use core::fmt::Debug;
+
+use crate::{DateTime, Tick, TimeSpan, Uptime, Watch};
+
+/// The time of an event in an `EventLog`.
+pub enum EventTime<T: Tick> {
+    /// The wall clock time of the event.
+    Wall(DateTime),
+    /// No wall clock is available, only the upstamp of the event.
+    Uptime(TimeSpan<T>),
+}
+
+/// A fixed capacity log of events that are timestamped with their upstamp.
+///
+/// The upstamps are converted to wall clock time when the events are read,
+/// so events recorded before the watch is set get their time once it is.
+/// When the log is full, the oldest event is replaced.
+pub struct EventLog<T: Tick, P, const N: usize> {
+    /// A ring of events, `None` where no event is stored.
+    events: [Option<(TimeSpan<T>, P)>; N],
+    /// The index of the oldest event.
+    oldest: usize,
+    /// The number of events in the log.
+    len: usize,
+    /// The number of events that were replaced because the log was full.
+    overwritten: u32,
+}
+
+impl<T: Tick, P, const N: usize> EventLog<T, P, N> {
+    /// Create a new empty `EventLog`.
+    pub fn new() -> Self {
+        assert!(N > 0, "The log must hold at least one event.");
+        Self {
+            events: [(); N].map(|_| None),
+            oldest: 0,
+            len: 0,
+            overwritten: 0,
+        }
+    }
+
+    /// Add an event with `payload` that happened at `upstamp`.
+    pub fn push(&mut self, upstamp: TimeSpan<T>, payload: P) {
+        let index = (self.oldest + self.len) % N;
+        self.events[index] = Some((upstamp, payload));
+
+        if self.len == N {
+            self.oldest = (self.oldest + 1) % N;
+            self.overwritten = self.overwritten.wrapping_add(1);
+        } else {
+            self.len += 1;
+        }
+    }
+
+    /// Remove the oldest event, and get its time according to `watch`.
+    pub fn pop<U: Uptime<T>, const M: usize>(
+        &mut self,
+        watch: &Watch<U, T, M>,
+    ) -> Option<(EventTime<T>, P)> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let (upstamp, payload) = self.events[self.oldest].take().unwrap();
+        self.oldest = (self.oldest + 1) % N;
+        self.len -= 1;
+        Some((event_time(watch, upstamp), payload))
+    }
+
+    /// Get the events from the oldest to the newest, and their time according to `watch`.
+    pub fn iter<'a, U: Uptime<T>, const M: usize>(
+        &'a self,
+        watch: &'a Watch<U, T, M>,
+    ) -> impl Iterator<Item = (EventTime<T>, &'a P)> + 'a {
+        (0..self.len).map(move |age| {
+            let (upstamp, payload) = self.events[(self.oldest + age) % N].as_ref().unwrap();
+            (event_time(watch, *upstamp), payload)
+        })
+    }
+
+    /// Get the number of events in the log.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get whether the log is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the number of events that were replaced because the log was full.
+    pub fn overwritten(&self) -> u32 {
+        self.overwritten
+    }
+}
+
+impl<T: Tick, P, const N: usize> Default for EventLog<T, P, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn event_time<U: Uptime<T>, T: Tick, const M: usize>(
+    watch: &Watch<U, T, M>,
+    upstamp: TimeSpan<T>,
+) -> EventTime<T> {
+    match watch.at(upstamp) {
+        Ok(datetime) => EventTime::Wall(datetime),
+        Err(_) => EventTime::Uptime(upstamp),
+    }
+}
+
+impl<T: Tick> Copy for EventTime<T> {}
+
+impl<T: Tick> Clone for EventTime<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Tick> PartialEq for EventTime<T> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Wall(a), Self::Wall(b)) => a == b,
+            (Self::Uptime(a), Self::Uptime(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<T: Tick> Debug for EventTime<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Wall(datetime) => f.debug_tuple("Wall").field(datetime).finish(),
+            Self::Uptime(upstamp) => f.debug_tuple("Uptime").field(upstamp).finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use alloc::sync::Arc;
+
+    use crate::{uptime::fakes::FakeUptime, Month};
+
+    use super::*;
+
+    struct TestTick;
+
+    impl Tick for TestTick {
+        const FREQ: u32 = 32768;
+    }
+
+    #[test]
+    fn convert_when_set() {
+        let watch = Watch::new(Arc::new(FakeUptime::new()));
+        let mut log = EventLog::<TestTick, u8, 4>::new();
+        let second = TimeSpan::<TestTick>::from_secs(1);
+
+        log.push(second, 1);
+        log.push(second + second, 2);
+        assert_eq!(
+            vec![
+                (EventTime::Uptime(second), &1),
+                (EventTime::Uptime(second + second), &2)
+            ],
+            log.iter(&watch).collect::<Vec<_>>()
+        );
+
+        // The events get their wall clock time once the watch is set.
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        watch.set(datetime, TimeSpan::from_secs(10));
+        assert_eq!(
+            Some((
+                EventTime::Wall(datetime - TimeSpan::<TestTick>::from_secs(9)),
+                1
+            )),
+            log.pop(&watch)
+        );
+        assert_eq!(
+            Some((
+                EventTime::Wall(datetime - TimeSpan::<TestTick>::from_secs(8)),
+                2
+            )),
+            log.pop(&watch)
+        );
+        assert_eq!(None, log.pop(&watch));
+    }
+
+    #[test]
+    fn overwrite_oldest() {
+        let watch = Watch::new(Arc::new(FakeUptime::new()));
+        let mut log = EventLog::<TestTick, u8, 2>::new();
+
+        log.push(TimeSpan::from_ticks(1), 1);
+        log.push(TimeSpan::from_ticks(2), 2);
+        log.push(TimeSpan::from_ticks(3), 3);
+
+        assert_eq!(2, log.len());
+        assert_eq!(1, log.overwritten());
+        assert_eq!(
+            vec![&2, &3],
+            log.iter(&watch)
+                .map(|(_, payload)| payload)
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use core::{
+    cell::UnsafeCell,
+    ptr,
+    sync::atomic::{fence, AtomicU32, Ordering},
+};
+
+/// A double buffered value that can be read from any thread without ever waiting for the writer.
+///
+/// The sequence number selects the copy that readers use while the writer updates the other one.
+/// A reader that preempts the writer therefore always finds a consistent copy,
+/// and a reader that is preempted by the writer simply retries.
+pub(crate) struct Latch<V: Copy> {
+    seq: AtomicU32,
+    copies: [UnsafeCell<V>; 2],
+}
+
+unsafe impl<V: Copy + Send> Sync for Latch<V> {}
+
+impl<V: Copy> Latch<V> {
+    /// Create a new `Latch` holding `value`.
+    pub(crate) const fn new(value: V) -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+            copies: [UnsafeCell::new(value), UnsafeCell::new(value)],
+        }
+    }
+
+    /// Get a consistent copy of the value.
+    pub(crate) fn read(&self) -> V {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            let value = unsafe { ptr::read_volatile(self.copies[(seq & 1) as usize].get()) };
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == seq {
+                break value;
+            } else {
+                // The writer preempted us and may have modified the copy while it was read, retry.
+            }
+        }
+    }
+
+    /// Replace the value.
+    ///
+    /// # Safety
+    ///
+    /// There must never be more than one thread writing at the same time.
+    pub(crate) unsafe fn write(&self, value: V) {
+        let seq = self.seq.load(Ordering::Relaxed);
+
+        // Direct readers to the odd copy while the even copy is written.
+        self.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+        ptr::write_volatile(self.copies[0].get(), value);
+
+        // Direct readers to the even copy while the odd copy is written.
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+        fence(Ordering::Release);
+        ptr::write_volatile(self.copies[1].get(), value);
+    }
+}
@@ -1,5 +1,6 @@
 #![feature(asm)]
 #![feature(const_fn_trait_bound)]
+#![feature(const_generics_defaults)]
 #![feature(never_type)]
 #![feature(prelude_import)]
 #![cfg_attr(not(feature = "std"), no_std)]
@@ -8,8 +9,21 @@ extern crate alloc;
 
 mod adapters;
 mod alarm;
+mod capture_drv;
 mod datetime;
+mod dcf77;
 pub mod drivers;
+mod edge;
+mod event_log;
+mod latch;
+mod listeners;
+mod low_power_uptime;
+mod persist;
+mod pulse_meter;
+mod sample;
+mod selector;
+mod synchronizer;
+mod time_source;
 mod timeout;
 mod timespan;
 mod uptime;
@@ -18,13 +32,26 @@ mod watch;
 
 pub use self::{
     adapters::alarm::{AlarmCounter, AlarmTimer, AlarmTimerMode},
+    adapters::capture::CaptureChannel,
+    adapters::cascade::{CascadeCounter, CascadeOverflow},
     adapters::tick::Tick,
     adapters::uptime::{UptimeCounter, UptimeOverflow},
-    alarm::AlarmDrv,
+    alarm::{AlarmDrv, Suspension},
+    capture_drv::{Capture, CaptureDrv, CaptureOverrun, Captures},
+    dcf77::{Dcf77Decoder, Dcf77Error, Dcf77Time},
+    edge::Edge,
+    event_log::{EventLog, EventTime},
+    low_power_uptime::LowPowerUptime,
+    persist::{RestoreError, WATCH_STATE_SIZE},
     prelude::*,
+    pulse_meter::{Measurement, PulseMeter},
+    sample::{TimeQuality, TimeSample, TimeSourceKind},
+    selector::{SelectError, Selection, Selector},
+    synchronizer::{SyncStatus, Synchronizer},
+    time_source::TimeSource,
     timeout::Timeout,
     uptime_drv::UptimeDrv,
-    watch::Watch,
+    watch::{Watch, WatchEvent, WatchEvents},
 };
 
 pub mod prelude {
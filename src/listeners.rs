@@ -0,0 +1,171 @@
+use alloc::boxed::Box;
+use atomicbox::AtomicOptionBox;
+use core::{
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+    task::Waker,
+};
+
+/// A list of listeners that are woken together.
+///
+/// Each listener claims a slot in the list for as long as it lives, and releases it for reuse when dropped,
+/// so the list only grows to the largest number of listeners alive at the same time.
+/// Slots are only ever added to the list, and claimed and released with atomics,
+/// so all of it can be done from any interrupt priority without a lock.
+pub(crate) struct Listeners {
+    head: AtomicPtr<Slot>,
+}
+
+/// A listener with a slot in `Listeners`, which is released when the listener is dropped.
+pub(crate) struct Listener<'a> {
+    slot: &'a Slot,
+}
+
+struct Slot {
+    /// Whether the slot is claimed by a listener.
+    claimed: AtomicBool,
+    /// The waker to invoke when the list is woken.
+    waker: AtomicOptionBox<Waker>,
+    /// The next slot in the list, which never changes once the slot is added.
+    next: *mut Slot,
+}
+
+impl Listeners {
+    /// Create a new empty list.
+    pub(crate) const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Create a new listener, reusing a slot released by a dropped listener if any.
+    pub(crate) fn listen(&self) -> Listener<'_> {
+        let mut slot = self.head.load(Ordering::Acquire);
+        while !slot.is_null() {
+            let released = unsafe { &*slot };
+            if released
+                .claimed
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return Listener { slot: released };
+            }
+            slot = released.next;
+        }
+
+        let slot = Box::into_raw(Box::new(Slot {
+            claimed: AtomicBool::new(true),
+            waker: AtomicOptionBox::new(None),
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*slot).next = head };
+            match self
+                .head
+                .compare_exchange_weak(head, slot, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+        Listener {
+            slot: unsafe { &*slot },
+        }
+    }
+
+    /// Wake all listeners that have registered a waker since they were last woken.
+    pub(crate) fn wake_all(&self) {
+        let mut slot = self.head.load(Ordering::Acquire);
+        while !slot.is_null() {
+            let listener = unsafe { &*slot };
+            if let Some(waker) = listener.waker.take(Ordering::AcqRel) {
+                waker.wake();
+            }
+            slot = listener.next;
+        }
+    }
+}
+
+impl Drop for Listeners {
+    fn drop(&mut self) {
+        let mut slot = *self.head.get_mut();
+        while !slot.is_null() {
+            let Slot { next, .. } = *unsafe { Box::from_raw(slot) };
+            slot = next;
+        }
+    }
+}
+
+unsafe impl Send for Listeners {}
+unsafe impl Sync for Listeners {}
+
+unsafe impl Send for Slot {}
+unsafe impl Sync for Slot {}
+
+impl Listener<'_> {
+    /// Register `waker` to be woken by the next call to `wake_all()`.
+    pub(crate) fn register(&self, waker: &Waker) {
+        self.slot
+            .waker
+            .store(Some(Box::new(waker.clone())), Ordering::AcqRel);
+    }
+}
+
+impl Drop for Listener<'_> {
+    fn drop(&mut self) {
+        // Drop the waker before the slot is released, so that it is not invoked for the next listener.
+        self.slot.waker.take(Ordering::AcqRel);
+        self.slot.claimed.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use alloc::{sync::Arc, task::Wake};
+    use core::sync::atomic::AtomicU32;
+
+    use super::*;
+
+    struct CountingWaker(AtomicU32);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn len(listeners: &Listeners) -> usize {
+        let mut len = 0;
+        let mut slot = listeners.head.load(Ordering::Acquire);
+        while !slot.is_null() {
+            len += 1;
+            slot = unsafe { (*slot).next };
+        }
+        len
+    }
+
+    #[test]
+    fn dropped_listeners_are_reused() {
+        let listeners = Listeners::new();
+        let counter = Arc::new(CountingWaker(AtomicU32::new(0)));
+        let waker = counter.clone().into();
+
+        let first = listeners.listen();
+        for _ in 0..10 {
+            let second = listeners.listen();
+            second.register(&waker);
+        }
+        assert_eq!(2, len(&listeners));
+
+        // The waker of a dropped listener is not invoked.
+        listeners.wake_all();
+        assert_eq!(0, counter.0.load(Ordering::Relaxed));
+
+        first.register(&waker);
+        first.register(&waker);
+        listeners.wake_all();
+        listeners.wake_all();
+        assert_eq!(1, counter.0.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,166 @@
+use alloc::sync::Arc;
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{latch::Latch, Tick, TimeSpan, Uptime};
+
+/// An uptime that stays continuous while the MCU is in a low power mode where the high resolution timer halts.
+///
+/// The time is read from the `high` resolution uptime while running,
+/// and the time spent in the low power mode is measured by the `low` power uptime,
+/// e.g. one driven by an LPTIM or the RTC that keeps counting in Stop mode, see `LptimUptimeDrv`.
+pub struct LowPowerUptime<H: Uptime<T>, L: Uptime<LT>, T: Tick, LT: Tick> {
+    high: Arc<H>,
+    low: Arc<L>,
+    state: Latch<State<T, LT>>,
+    /// Whether `suspend()` or `resume()` is currently writing `state`.
+    writing: AtomicBool,
+}
+
+struct State<T: Tick, LT: Tick> {
+    /// The time that is added to the high resolution uptime.
+    offset: TimeSpan<T>,
+    /// The high and low power uptimes when suspended, `None` if running.
+    suspended: Option<(TimeSpan<T>, TimeSpan<LT>)>,
+    tick: PhantomData<LT>,
+}
+
+impl<H: Uptime<T>, L: Uptime<LT>, T: Tick, LT: Tick> LowPowerUptime<H, L, T, LT> {
+    /// Create a new `LowPowerUptime` merging the `high` resolution uptime with the `low` power uptime.
+    pub fn new(high: Arc<H>, low: Arc<L>) -> Self {
+        Self {
+            high,
+            low,
+            state: Latch::new(State {
+                offset: TimeSpan::ZERO,
+                suspended: None,
+                tick: PhantomData,
+            }),
+            writing: AtomicBool::new(false),
+        }
+    }
+
+    /// Prepare for entering the low power mode.
+    /// Until `resume()`, the time is measured by the low power uptime.
+    pub fn suspend(&self) {
+        self.write(|state| {
+            if state.suspended.is_none() {
+                state.suspended = Some((self.high.now(), self.low.now()));
+            }
+        });
+    }
+
+    /// Resume after leaving the low power mode, continuing the time measured by the low power uptime.
+    pub fn resume(&self) {
+        self.write(|state| {
+            if let Some((high, low)) = state.suspended.take() {
+                let slept = convert::<LT, T>(self.low.now() - low);
+                let lost = high + slept - self.high.now();
+                if lost > TimeSpan::ZERO {
+                    // The high resolution timer was halted for some of the time.
+                    state.offset += lost;
+                }
+            }
+        });
+    }
+
+    fn write(&self, f: impl FnOnce(&mut State<T, LT>)) {
+        assert!(
+            !self.writing.swap(true, Ordering::Acquire),
+            "suspend() and resume() must not be called concurrently."
+        );
+        let mut state = self.state.read();
+        f(&mut state);
+        unsafe { self.state.write(state) };
+        self.writing.store(false, Ordering::Release);
+    }
+}
+
+impl<H: Uptime<T>, L: Uptime<LT>, T: Tick, LT: Tick> Uptime<T> for LowPowerUptime<H, L, T, LT> {
+    fn counter(&self) -> u32 {
+        self.high.counter()
+    }
+
+    fn now(&self) -> TimeSpan<T> {
+        let state = self.state.read();
+        match state.suspended {
+            Some((high, low)) => state.offset + high + convert::<LT, T>(self.low.now() - low),
+            None => state.offset + self.high.now(),
+        }
+    }
+
+    fn at(&self, counter: u32) -> TimeSpan<T> {
+        self.state.read().offset + self.high.at(counter)
+    }
+}
+
+/// Convert `span` from ticks of `From` to ticks of `To`.
+fn convert<From: Tick, To: Tick>(span: TimeSpan<From>) -> TimeSpan<To> {
+    TimeSpan::from_ticks((span.0 as i128 * To::FREQ as i128 / From::FREQ as i128) as i64)
+}
+
+impl<T: Tick, LT: Tick> Copy for State<T, LT> {}
+
+impl<T: Tick, LT: Tick> Clone for State<T, LT> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::uptime::fakes::FakeUptime;
+
+    use super::*;
+
+    struct HighTick;
+
+    impl Tick for HighTick {
+        const FREQ: u32 = 1_000_000;
+    }
+
+    struct LowTick;
+
+    impl Tick for LowTick {
+        const FREQ: u32 = 32768;
+    }
+
+    #[test]
+    fn continuous_across_sleep() {
+        let high = Arc::new(FakeUptime::<HighTick>::new());
+        let low = Arc::new(FakeUptime::<LowTick>::new());
+        let uptime = LowPowerUptime::new(high.clone(), low.clone());
+
+        high.set(TimeSpan::from_secs(1));
+        low.set(TimeSpan::from_secs(1));
+        assert_eq!(TimeSpan::from_secs(1), uptime.now());
+
+        // The high resolution timer halts while sleeping.
+        uptime.suspend();
+        low.set(TimeSpan::from_secs(3));
+        assert_eq!(TimeSpan::from_secs(3), uptime.now());
+        uptime.resume();
+        assert_eq!(TimeSpan::from_secs(3), uptime.now());
+
+        high.set(TimeSpan::from_millis(1_500));
+        low.set(TimeSpan::from_millis(3_500));
+        assert_eq!(TimeSpan::from_millis(3_500), uptime.now());
+        assert_eq!(TimeSpan::from_millis(3_500), uptime.at(1_500_000));
+    }
+
+    #[test]
+    fn monotonic_when_not_halted() {
+        let high = Arc::new(FakeUptime::<HighTick>::new());
+        let low = Arc::new(FakeUptime::<LowTick>::new());
+        let uptime = LowPowerUptime::new(high.clone(), low.clone());
+
+        // The high resolution timer kept running and is a bit ahead of the low power uptime.
+        uptime.suspend();
+        low.set(TimeSpan::from_secs(1));
+        high.set(TimeSpan::from_millis(1_001));
+        uptime.resume();
+        assert_eq!(TimeSpan::from_millis(1_001), uptime.now());
+    }
+}
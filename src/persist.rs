@@ -0,0 +1,187 @@
+use crate::{DateTime, TimeSourceKind};
+
+/// The size of a persisted `Watch` state.
+pub const WATCH_STATE_SIZE: usize = 32;
+
+const MAGIC: u32 = 0x5754_4344;
+const VERSION: u8 = 1;
+
+/// Whether the drift estimate is present.
+const FLAG_DRIFT: u8 = 1 << 0;
+
+/// The state of a `Watch` that survives a reset.
+///
+/// The state is encoded as little endian:
+///
+/// | Offset | Size | Content                                            |
+/// |--------|------|----------------------------------------------------|
+/// | 0      | 4    | Magic                                              |
+/// | 4      | 1    | Version                                            |
+/// | 5      | 1    | Flags                                              |
+/// | 6      | 1    | Time source                                        |
+/// | 7      | 1    | Reserved                                           |
+/// | 8      | 4    | Tick frequency                                     |
+/// | 12     | 8    | Wall clock time in ticks since the unix epoch      |
+/// | 20     | 4    | Retained time source reading at the same time      |
+/// | 24     | 4    | Drift estimate in parts per billion                |
+/// | 28     | 4    | CRC-32 of the preceding bytes                      |
+pub(crate) struct State {
+    pub(crate) freq: u32,
+    pub(crate) wall: i64,
+    pub(crate) retained: DateTime,
+    pub(crate) drift_ppb: Option<i32>,
+    pub(crate) source: TimeSourceKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RestoreError {
+    /// The state was never persisted.
+    InvalidMagic,
+    /// The state was persisted by an incompatible version.
+    UnsupportedVersion,
+    /// The state is corrupted.
+    Crc,
+    /// The state was persisted with a different tick frequency.
+    FrequencyMismatch,
+}
+
+impl State {
+    pub(crate) fn encode(&self) -> [u8; WATCH_STATE_SIZE] {
+        let mut bytes = [0; WATCH_STATE_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        bytes[4] = VERSION;
+        bytes[5] = if self.drift_ppb.is_some() {
+            FLAG_DRIFT
+        } else {
+            0
+        };
+        bytes[6] = source_to_u8(self.source);
+        bytes[8..12].copy_from_slice(&self.freq.to_le_bytes());
+        bytes[12..20].copy_from_slice(&self.wall.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.retained.unixtimestamp().to_le_bytes());
+        bytes[24..28].copy_from_slice(&self.drift_ppb.unwrap_or(0).to_le_bytes());
+        let crc = crc32(&bytes[0..28]);
+        bytes[28..32].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    pub(crate) fn decode(bytes: &[u8; WATCH_STATE_SIZE]) -> Result<Self, RestoreError> {
+        if u32::from_le_bytes(bytes[0..4].try_into().unwrap()) != MAGIC {
+            return Err(RestoreError::InvalidMagic);
+        }
+        if bytes[4] != VERSION {
+            return Err(RestoreError::UnsupportedVersion);
+        }
+        if u32::from_le_bytes(bytes[28..32].try_into().unwrap()) != crc32(&bytes[0..28]) {
+            return Err(RestoreError::Crc);
+        }
+
+        let drift_ppb = i32::from_le_bytes(bytes[24..28].try_into().unwrap());
+        Ok(Self {
+            freq: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            wall: i64::from_le_bytes(bytes[12..20].try_into().unwrap()),
+            retained: DateTime::from_unixtimestamp(u32::from_le_bytes(
+                bytes[20..24].try_into().unwrap(),
+            )),
+            drift_ppb: if bytes[5] & FLAG_DRIFT != 0 {
+                Some(drift_ppb)
+            } else {
+                None
+            },
+            source: source_from_u8(bytes[6]),
+        })
+    }
+}
+
+fn source_to_u8(source: TimeSourceKind) -> u8 {
+    match source {
+        TimeSourceKind::Rtc => 0,
+        TimeSourceKind::Gnss => 1,
+        TimeSourceKind::Ntp => 2,
+        TimeSourceKind::Radio => 3,
+        TimeSourceKind::Manual => 4,
+    }
+}
+
+fn source_from_u8(value: u8) -> TimeSourceKind {
+    match value {
+        1 => TimeSourceKind::Gnss,
+        2 => TimeSourceKind::Ntp,
+        3 => TimeSourceKind::Radio,
+        4 => TimeSourceKind::Manual,
+        _ => TimeSourceKind::Rtc,
+    }
+}
+
+/// Compute the CRC-32 (IEEE 802.3) of `bytes`.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::Month;
+
+    use super::*;
+
+    #[test]
+    fn crc() {
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+    }
+
+    #[test]
+    fn encode_decode() {
+        let state = State {
+            freq: 32768,
+            wall: -123456789,
+            retained: DateTime::new(2021, Month::January, 8, 10, 39, 27),
+            drift_ppb: Some(-42000),
+            source: TimeSourceKind::Gnss,
+        };
+
+        let decoded = State::decode(&state.encode()).unwrap();
+        assert_eq!(state.freq, decoded.freq);
+        assert_eq!(state.wall, decoded.wall);
+        assert_eq!(state.retained, decoded.retained);
+        assert_eq!(state.drift_ppb, decoded.drift_ppb);
+        assert_eq!(state.source, decoded.source);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let state = State {
+            freq: 32768,
+            wall: 0,
+            retained: DateTime::EPOCH,
+            drift_ppb: None,
+            source: TimeSourceKind::Rtc,
+        };
+
+        let mut bytes = state.encode();
+        bytes[12] ^= 1;
+        assert_eq!(RestoreError::Crc, State::decode(&bytes).err().unwrap());
+
+        let mut bytes = state.encode();
+        bytes[4] = 2;
+        assert_eq!(
+            RestoreError::UnsupportedVersion,
+            State::decode(&bytes).err().unwrap()
+        );
+
+        assert_eq!(
+            RestoreError::InvalidMagic,
+            State::decode(&[0; WATCH_STATE_SIZE]).err().unwrap()
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use futures::{Stream, StreamExt};
+
+use crate::{Capture, CaptureOverrun, Edge, Tick, TimeSpan};
+
+/// The outlier tolerance in percent of the median.
+const TOLERANCE_PERCENT: u32 = 25;
+
+/// A measurement of a periodic signal.
+pub struct Measurement<T: Tick> {
+    /// The averaged period of the signal.
+    pub period: TimeSpan<T>,
+    /// The averaged time that the signal is high in each period,
+    /// `None` if only one of the edges is captured.
+    pub width: Option<TimeSpan<T>>,
+}
+
+/// Measures the period and pulse width of a signal from a stream of its captured edges.
+///
+/// The periods are measured between edges of the kind that is captured first,
+/// and the pulse widths from a rising edge to the following falling edge.
+/// Each measurement is the average over `N` periods where outliers are rejected.
+/// As the edges are upstamps, periods can span any number of timer overflows.
+pub struct PulseMeter<S, T: Tick, const N: usize> {
+    captures: S,
+    /// The outlier tolerance in percent of the median.
+    tolerance_percent: u32,
+    /// The kind of the edges that the periods are measured between, `None` until the first capture.
+    period_edge: Option<Edge>,
+    /// The upstamp of the last rising edge.
+    rising: Option<TimeSpan<T>>,
+    /// The upstamp of the last falling edge.
+    falling: Option<TimeSpan<T>>,
+}
+
+impl<S, T, const N: usize> PulseMeter<S, T, N>
+where
+    S: Stream<Item = Result<Capture<T>, CaptureOverrun>> + Unpin,
+    T: Tick,
+{
+    /// Create a new `PulseMeter` over a stream of `captures`.
+    pub fn new(captures: S) -> Self {
+        assert!(N > 0, "A measurement must span at least one period.");
+        Self {
+            captures,
+            tolerance_percent: TOLERANCE_PERCENT,
+            period_edge: None,
+            rising: None,
+            falling: None,
+        }
+    }
+
+    /// Reject periods and widths that deviate more than `percent` from the median.
+    pub fn with_tolerance(mut self, percent: u32) -> Self {
+        self.tolerance_percent = percent;
+        self
+    }
+
+    /// Measure the signal over the next `N` periods.
+    /// Returns `None` if the stream of captures ends.
+    pub async fn measure(&mut self) -> Option<Measurement<T>> {
+        let mut periods = [TimeSpan::ZERO; N];
+        let mut period_count = 0;
+        let mut widths = [TimeSpan::ZERO; N];
+        let mut width_count = 0;
+
+        while period_count < N {
+            let capture = match self.captures.next().await? {
+                Ok(capture) => capture,
+                Err(CaptureOverrun) => {
+                    // An edge is lost, so the next intervals would span it.
+                    self.rising = None;
+                    self.falling = None;
+                    continue;
+                }
+            };
+
+            let last = match capture.edge {
+                Edge::Rising => self.rising.replace(capture.upstamp),
+                Edge::Falling => {
+                    if let Some(rising) = self.rising {
+                        if width_count < N {
+                            widths[width_count] = capture.upstamp - rising;
+                            width_count += 1;
+                        }
+                    }
+                    self.falling.replace(capture.upstamp)
+                }
+            };
+
+            // Only measure the periods between one kind of edges, so that each period is one cycle of the signal.
+            if capture.edge == *self.period_edge.get_or_insert(capture.edge) {
+                if let Some(last) = last {
+                    periods[period_count] = capture.upstamp - last;
+                    period_count += 1;
+                }
+            }
+        }
+
+        Some(Measurement {
+            period: self.average(&mut periods)?,
+            width: self.average(&mut widths[..width_count]),
+        })
+    }
+
+    /// Get the average of `spans` that are within the tolerance of their median.
+    fn average(&self, spans: &mut [TimeSpan<T>]) -> Option<TimeSpan<T>> {
+        if spans.is_empty() {
+            return None;
+        }
+
+        spans.sort_unstable_by_key(|span| span.0);
+        let median = spans[spans.len() / 2].0;
+        let tolerance = median * self.tolerance_percent as i64 / 100;
+
+        let (sum, count) = spans
+            .iter()
+            .filter(|span| (span.0 - median).abs() <= tolerance)
+            .fold((0, 0), |(sum, count), span| (sum + span.0, count + 1));
+        Some(TimeSpan::from_ticks(sum / count))
+    }
+}
+
+impl<T: Tick> Measurement<T> {
+    /// Get the frequency of the signal in Hz.
+    pub fn frequency(&self) -> f32 {
+        T::FREQ as f32 / self.period.0 as f32
+    }
+
+    /// Get the fraction of the period that the signal is high.
+    pub fn duty_cycle(&self) -> Option<f32> {
+        self.width
+            .map(|width| width.0 as f32 / self.period.0 as f32)
+    }
+}
+
+impl<T: Tick> Copy for Measurement<T> {}
+
+impl<T: Tick> Clone for Measurement<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use alloc::vec::Vec;
+    use futures::{stream, FutureExt};
+
+    use super::*;
+
+    struct TestTick;
+
+    impl Tick for TestTick {
+        const FREQ: u32 = 1_000_000;
+    }
+
+    fn edge(edge: Edge, upstamp: i64) -> Result<Capture<TestTick>, CaptureOverrun> {
+        Ok(Capture {
+            edge,
+            upstamp: TimeSpan::from_ticks(upstamp),
+        })
+    }
+
+    /// A signal with a `period` that is high for `width`, with each period offset by `jitter`.
+    fn signal(
+        period: i64,
+        width: i64,
+        jitter: &[i64],
+    ) -> Vec<Result<Capture<TestTick>, CaptureOverrun>> {
+        jitter
+            .iter()
+            .enumerate()
+            .flat_map(|(index, jitter)| {
+                let rising = index as i64 * period + jitter;
+                [
+                    edge(Edge::Rising, rising),
+                    edge(Edge::Falling, rising + width),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn measure() {
+        let captures = signal(1000, 250, &[0, 10, 0, 0, 0]);
+        let mut meter = PulseMeter::<_, _, 4>::new(stream::iter(captures));
+
+        let measurement = meter.measure().now_or_never().unwrap().unwrap();
+        assert_eq!(1000, measurement.period.0);
+        assert_eq!(250, measurement.width.unwrap().0);
+        assert_eq!(1000.0, measurement.frequency());
+        assert_eq!(Some(0.25), measurement.duty_cycle());
+    }
+
+    #[test]
+    fn reject_outliers() {
+        // The periods around the glitch at 1600 are rejected.
+        let mut captures = signal(1000, 500, &[0; 8]);
+        captures.insert(4, edge(Edge::Rising, 1600));
+        let mut meter = PulseMeter::<_, _, 6>::new(stream::iter(captures));
+
+        let measurement = meter.measure().now_or_never().unwrap().unwrap();
+        assert_eq!(1000, measurement.period.0);
+        assert_eq!(500, measurement.width.unwrap().0);
+    }
+
+    #[test]
+    fn falling_edges_only() {
+        let captures = signal(1000, 250, &[0, 0, 0])
+            .into_iter()
+            .filter(|capture| matches!(capture, Ok(capture) if capture.edge == Edge::Falling))
+            .collect::<Vec<_>>();
+        let mut meter = PulseMeter::<_, _, 2>::new(stream::iter(captures));
+
+        let measurement = meter.measure().now_or_never().unwrap().unwrap();
+        assert_eq!(1000, measurement.period.0);
+        assert!(measurement.width.is_none());
+    }
+
+    #[test]
+    fn long_periods() {
+        // The periods span many overflows of a 16-bit timer.
+        let captures = signal(10_000_000, 1_000, &[0, 0, 0]);
+        let mut meter = PulseMeter::<_, _, 2>::new(stream::iter(captures));
+
+        let measurement = meter.measure().now_or_never().unwrap().unwrap();
+        assert_eq!(10_000_000, measurement.period.0);
+        assert_eq!(0.1, measurement.frequency());
+    }
+
+    #[test]
+    fn overrun() {
+        let mut captures = signal(1000, 500, &[0, 0, 0, 0]);
+        captures[3] = Err(CaptureOverrun);
+        let mut meter = PulseMeter::<_, _, 2>::new(stream::iter(captures));
+
+        // The periods are not measured across the overrun.
+        let measurement = meter.measure().now_or_never().unwrap().unwrap();
+        assert_eq!(1000, measurement.period.0);
+        assert!(meter.measure().now_or_never().unwrap().is_none());
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{DateTime, Tick, TimeSpan};
+
+/// The kind of source that a time is obtained from.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimeSourceKind {
+    /// A real time clock.
+    Rtc,
+    /// A satellite navigation receiver.
+    Gnss,
+    /// A network time server.
+    Ntp,
+    /// A long wave time signal, e.g. DCF77.
+    Radio,
+    /// The time was set by hand.
+    Manual,
+}
+
+/// A time obtained from a time source.
+pub struct TimeSample<T: Tick> {
+    /// The obtained time.
+    pub datetime: DateTime,
+    /// The upstamp at which `datetime` was the current time.
+    pub upstamp: TimeSpan<T>,
+    /// The maximum error of `datetime`.
+    pub error: TimeSpan<T>,
+    /// The kind of source that the time is obtained from.
+    pub source: TimeSourceKind,
+}
+
+/// The quality of a time read from a `Watch`.
+pub struct TimeQuality<T: Tick> {
+    /// The kind of source that the watch was last synchronized with.
+    pub source: TimeSourceKind,
+    /// The upstamp of the sample that the watch was last synchronized with.
+    pub synced: TimeSpan<T>,
+    /// The maximum error of the time.
+    pub error: TimeSpan<T>,
+    /// Whether the time is still within the holdover period since the last synchronization.
+    pub trusted: bool,
+}
+
+impl<T: Tick> Copy for TimeSample<T> {}
+
+impl<T: Tick> Clone for TimeSample<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Tick> Copy for TimeQuality<T> {}
+
+impl<T: Tick> Clone for TimeQuality<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
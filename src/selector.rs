@@ -0,0 +1,245 @@
+use crate::{
+    watch::{from_wall, to_wall},
+    Tick, TimeSample, TimeSpan, Uptime, Watch,
+};
+
+/// Selects the time from multiple time sources using the intersection algorithm.
+///
+/// Each sample defines an interval that the correct time is within.
+/// The selector finds the largest set of sources whose intervals overlap,
+/// and combines them into a single sample if they are a majority.
+/// The remaining sources are falsetickers.
+pub struct Selector {
+    /// The minimum number of sources that must agree.
+    min_sources: usize,
+}
+
+/// The result of a selection.
+pub struct Selection<T: Tick, const N: usize> {
+    /// The combined sample of the agreeing sources.
+    pub sample: TimeSample<T>,
+    /// Whether each source agrees with the selected time.
+    /// Falsetickers and sources without a sample are `false`.
+    pub truechimers: [bool; N],
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SelectError {
+    /// Fewer sources than required agree.
+    TooFewSources,
+    /// The agreeing sources are not a majority of the sources with a sample.
+    NoMajority,
+}
+
+impl Selector {
+    /// Create a new `Selector` that requires at least one source.
+    pub const fn new() -> Self {
+        Self { min_sources: 1 }
+    }
+
+    /// Require at least `min_sources` agreeing sources.
+    pub const fn with_min_sources(mut self, min_sources: usize) -> Self {
+        self.min_sources = min_sources;
+        self
+    }
+
+    /// Select the time from `samples`, one for each source, `None` if the source has no sample.
+    pub fn select<T: Tick, const N: usize>(
+        &self,
+        samples: &[Option<TimeSample<T>>; N],
+    ) -> Result<Selection<T, N>, SelectError> {
+        // Compare all samples at the upstamp of the newest one.
+        let upstamp = samples
+            .iter()
+            .flatten()
+            .map(|sample| sample.upstamp)
+            .reduce(|a, b| if b > a { b } else { a })
+            .ok_or(SelectError::TooFewSources)?;
+
+        let mut intervals = [None; N];
+        for (interval, sample) in intervals.iter_mut().zip(samples) {
+            if let Some(sample) = sample {
+                let wall = to_wall::<T>(sample.datetime) + (upstamp - sample.upstamp);
+                let error = sample.error.abs();
+                *interval = Some((wall - error, wall + error));
+            }
+        }
+
+        // Find the lower bound that is within the most intervals.
+        let mut best = None;
+        let mut best_count = 0;
+        for (low, _) in intervals.iter().flatten() {
+            let count = intervals
+                .iter()
+                .flatten()
+                .filter(|(l, h)| l <= low && low <= h)
+                .count();
+            if count > best_count {
+                best = Some(*low);
+                best_count = count;
+            }
+        }
+        let point = best.ok_or(SelectError::TooFewSources)?;
+
+        let mut truechimers = [false; N];
+        let mut low = TimeSpan::MIN;
+        let mut high = TimeSpan::MAX;
+        let mut best_source = None;
+        for (index, interval) in intervals.iter().enumerate() {
+            if let Some((l, h)) = *interval {
+                if l <= point && point <= h {
+                    truechimers[index] = true;
+                    if l > low {
+                        low = l;
+                    }
+                    if h < high {
+                        high = h;
+                    }
+
+                    let sample = samples[index].as_ref().unwrap();
+                    match best_source {
+                        Some((_, error)) if error <= sample.error.abs() => {}
+                        _ => best_source = Some((sample.source, sample.error.abs())),
+                    }
+                }
+            }
+        }
+
+        let valid = intervals.iter().flatten().count();
+        if best_count < self.min_sources {
+            return Err(SelectError::TooFewSources);
+        }
+        if best_count * 2 <= valid {
+            return Err(SelectError::NoMajority);
+        }
+
+        // The correct time is within the intersection of the truechimers.
+        let wall = low + TimeSpan::from_ticks((high - low).0 / 2);
+        let datetime = from_wall(wall);
+        Ok(Selection {
+            sample: TimeSample {
+                datetime,
+                upstamp: upstamp - (wall - to_wall(datetime)),
+                error: TimeSpan::from_ticks((high - low).0 - (high - low).0 / 2),
+                source: best_source.unwrap().0,
+            },
+            truechimers,
+        })
+    }
+
+    /// Select the time from `samples` and synchronize `watch` with it.
+    pub fn sync<U: Uptime<T>, T: Tick, const N: usize, const M: usize>(
+        &self,
+        watch: &Watch<U, T, M>,
+        samples: &[Option<TimeSample<T>>; N],
+    ) -> Result<Selection<T, N>, SelectError> {
+        let selection = self.select(samples)?;
+        watch.sync(selection.sample);
+        Ok(selection)
+    }
+}
+
+impl Default for Selector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::{uptime::fakes::FakeUptime, DateTime, Month, TimeSourceKind};
+
+    use super::*;
+
+    struct TestTick;
+
+    impl Tick for TestTick {
+        const FREQ: u32 = 32768;
+    }
+
+    fn sample(
+        datetime: DateTime,
+        upstamp: TimeSpan<TestTick>,
+        error_millis: i64,
+        source: TimeSourceKind,
+    ) -> Option<TimeSample<TestTick>> {
+        Some(TimeSample {
+            datetime,
+            upstamp,
+            error: TimeSpan::from_millis(error_millis),
+            source,
+        })
+    }
+
+    #[test]
+    fn select() {
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let second = TimeSpan::<TestTick>::from_secs(1);
+        let samples = [
+            sample(datetime, TimeSpan::ZERO, 500, TimeSourceKind::Rtc),
+            sample(datetime + second, second, 100, TimeSourceKind::Gnss),
+            // Taken a second earlier than the others.
+            sample(datetime, TimeSpan::ZERO, 200, TimeSourceKind::Ntp),
+            None,
+        ];
+
+        let selection = Selector::new().select(&samples).unwrap();
+        assert_eq!([true, true, true, false], selection.truechimers);
+        assert_eq!(datetime + second, selection.sample.datetime);
+        assert_eq!(second, selection.sample.upstamp);
+        assert_eq!(TimeSourceKind::Gnss, selection.sample.source);
+        assert!(selection.sample.error <= TimeSpan::from_millis(100));
+    }
+
+    #[test]
+    fn select_falseticker() {
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let second = TimeSpan::<TestTick>::from_secs(1);
+        let samples = [
+            sample(datetime, TimeSpan::ZERO, 100, TimeSourceKind::Gnss),
+            sample(datetime + second, TimeSpan::ZERO, 100, TimeSourceKind::Rtc),
+            sample(datetime, TimeSpan::ZERO, 500, TimeSourceKind::Ntp),
+        ];
+
+        let selection = Selector::new().select(&samples).unwrap();
+        assert_eq!([true, false, true], selection.truechimers);
+        assert_eq!(datetime, selection.sample.datetime);
+        assert_eq!(TimeSpan::ZERO, selection.sample.upstamp);
+    }
+
+    #[test]
+    fn select_no_majority() {
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let second = TimeSpan::<TestTick>::from_secs(1);
+        let samples = [
+            sample(datetime, TimeSpan::ZERO, 100, TimeSourceKind::Gnss),
+            sample(datetime + second, TimeSpan::ZERO, 100, TimeSourceKind::Rtc),
+        ];
+
+        assert_eq!(
+            SelectError::NoMajority,
+            Selector::new().select(&samples).err().unwrap()
+        );
+        assert_eq!(
+            SelectError::TooFewSources,
+            Selector::new()
+                .with_min_sources(2)
+                .select(&[samples[0]])
+                .err()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn sync() {
+        let uptime = FakeUptime::new();
+        let watch = Watch::new(alloc::sync::Arc::new(uptime));
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let samples = [sample(datetime, TimeSpan::ZERO, 100, TimeSourceKind::Gnss)];
+
+        Selector::new().sync(&watch, &samples).unwrap();
+        let (now, quality) = watch.now_with_quality().unwrap();
+        assert_eq!(datetime, now);
+        assert_eq!(TimeSourceKind::Gnss, quality.source);
+    }
+}
@@ -0,0 +1,195 @@
+use alloc::boxed::Box;
+
+use crate::{Alarm, Tick, TimeSample, TimeSource, TimeSpan, Uptime, Watch};
+
+/// The outcome of a synchronization attempt.
+pub enum SyncStatus<T: Tick, E> {
+    /// The watch was synchronized with `sample`.
+    Synced { sample: TimeSample<T> },
+    /// The time source failed with `error`, and is tried again after `retry`.
+    Failed { error: E, retry: TimeSpan<T> },
+}
+
+/// A callback that is called with the outcome of each synchronization attempt.
+type StatusCallback<T, E> = Box<dyn FnMut(SyncStatus<T, E>) + Send>;
+
+/// Periodically synchronizes a `Watch` with a `TimeSource`.
+///
+/// The source is polled every `interval` when it succeeds.
+/// When it fails, it is retried with an exponential backoff.
+pub struct Synchronizer<S: TimeSource<T>, T: Tick> {
+    source: S,
+    /// The time between successful synchronizations.
+    interval: TimeSpan<T>,
+    /// The delay before the first retry after a failure.
+    min_backoff: TimeSpan<T>,
+    /// The maximum delay between retries.
+    max_backoff: TimeSpan<T>,
+    /// The delay before the next retry, `None` if the last attempt succeeded.
+    backoff: Option<TimeSpan<T>>,
+    status: Option<StatusCallback<T, S::Error>>,
+}
+
+impl<S: TimeSource<T>, T: Tick> Synchronizer<S, T> {
+    /// Create a new `Synchronizer` that polls `source` every `interval`.
+    /// Failures are retried after a second, doubling the delay up to `interval`.
+    pub fn new(source: S, interval: TimeSpan<T>) -> Self {
+        Self {
+            source,
+            interval,
+            min_backoff: TimeSpan::from_secs(1),
+            max_backoff: interval,
+            backoff: None,
+            status: None,
+        }
+    }
+
+    /// Retry failures after `min`, doubling the delay for each failure up to `max`.
+    pub fn with_backoff(mut self, min: TimeSpan<T>, max: TimeSpan<T>) -> Self {
+        self.min_backoff = min;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Call `status` with the outcome of each synchronization attempt.
+    pub fn with_status(
+        mut self,
+        status: impl FnMut(SyncStatus<T, S::Error>) + Send + 'static,
+    ) -> Self {
+        self.status = Some(Box::new(status));
+        self
+    }
+
+    /// Synchronize `watch` forever, sleeping with `alarm` between the attempts.
+    pub async fn run<U: Uptime<T>, A: Alarm<T>, const N: usize>(
+        &mut self,
+        watch: &Watch<U, T, N>,
+        alarm: &A,
+    ) -> ! {
+        loop {
+            let delay = self.step(watch).await;
+            alarm.sleep(delay).await;
+        }
+    }
+
+    /// Make a single synchronization attempt, and get the delay until the next attempt.
+    pub async fn step<U: Uptime<T>, const N: usize>(
+        &mut self,
+        watch: &Watch<U, T, N>,
+    ) -> TimeSpan<T> {
+        let (status, delay) = match self.source.sample().await {
+            Ok(sample) => {
+                watch.sync(sample);
+                self.backoff = None;
+                (SyncStatus::Synced { sample }, self.interval)
+            }
+            Err(error) => {
+                let retry = match self.backoff {
+                    Some(backoff) if backoff + backoff < self.max_backoff => backoff + backoff,
+                    Some(_) => self.max_backoff,
+                    None => self.min_backoff,
+                };
+                self.backoff = Some(retry);
+                (SyncStatus::Failed { error, retry }, retry)
+            }
+        };
+
+        if let Some(callback) = self.status.as_mut() {
+            callback(status);
+        }
+
+        delay
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use alloc::{sync::Arc, vec::Vec};
+    use async_trait::async_trait;
+    use drone_core::sync::Mutex;
+    use futures::prelude::*;
+
+    use crate::{
+        adapters::alarm::fakes::{FakeAlarmCounter, FakeAlarmTimer, FakeTick},
+        uptime::fakes::FakeUptime,
+        AlarmDrv, DateTime, Month, TimeSourceKind,
+    };
+
+    use super::*;
+
+    /// A source that replays a list of outcomes, and then never completes.
+    struct FakeSource(Vec<Result<TimeSample<FakeTick>, ()>>);
+
+    #[async_trait]
+    impl TimeSource<FakeTick> for FakeSource {
+        type Error = ();
+
+        async fn sample(&mut self) -> Result<TimeSample<FakeTick>, Self::Error> {
+            if self.0.is_empty() {
+                future::pending().await
+            } else {
+                self.0.remove(0)
+            }
+        }
+    }
+
+    fn sample(datetime: DateTime) -> Result<TimeSample<FakeTick>, ()> {
+        Ok(TimeSample {
+            datetime,
+            upstamp: TimeSpan::ZERO,
+            error: TimeSpan::ZERO,
+            source: TimeSourceKind::Ntp,
+        })
+    }
+
+    #[test]
+    fn step() {
+        let watch = Watch::new(Arc::new(FakeUptime::new()));
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let mut synchronizer = Synchronizer::new(
+            FakeSource(vec![Err(()), Err(()), Err(()), sample(datetime), Err(())]),
+            TimeSpan::from_secs(60),
+        )
+        .with_backoff(TimeSpan::from_secs(10), TimeSpan::from_secs(30));
+
+        let mut step = || synchronizer.step(&watch).now_or_never().unwrap().0;
+        assert_eq!(10, step());
+        assert_eq!(20, step());
+        assert_eq!(30, step());
+        assert!(watch.now().is_err());
+        assert_eq!(60, step());
+        assert_eq!(datetime, watch.now().unwrap());
+
+        // The backoff starts over after a successful synchronization.
+        assert_eq!(10, step());
+    }
+
+    #[test]
+    fn run() {
+        let watch = Watch::new(Arc::new(FakeUptime::new()));
+        let alarm = AlarmDrv::new(
+            FakeAlarmCounter(0),
+            FakeAlarmTimer {
+                compares: Vec::new(),
+            },
+            FakeTick,
+        );
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+        let log = statuses.clone();
+        let mut synchronizer = Synchronizer::new(
+            FakeSource(vec![Err(()), sample(datetime)]),
+            TimeSpan::from_secs(60),
+        )
+        .with_status(move |status| {
+            log.try_lock().unwrap().push(match status {
+                SyncStatus::Synced { sample } => Ok(sample.datetime),
+                SyncStatus::Failed { retry, .. } => Err(retry.0),
+            });
+        });
+
+        assert!(synchronizer.run(&watch, &alarm).now_or_never().is_none());
+        assert_eq!(vec![Err(1), Ok(datetime)], *statuses.try_lock().unwrap());
+        assert_eq!(datetime, watch.now().unwrap());
+    }
+}
@@ -0,0 +1,12 @@
+use async_trait::async_trait;
+
+use crate::{Tick, TimeSample};
+
+/// A source that the time can be obtained from, e.g. a GNSS receiver, an NTP server or a real time clock.
+#[async_trait]
+pub trait TimeSource<T: Tick>: Send {
+    type Error;
+
+    /// Obtain a sample of the current time.
+    async fn sample(&mut self) -> Result<TimeSample<T>, Self::Error>;
+}
@@ -11,3 +11,47 @@ pub trait Uptime<T: Tick>: Send + Sync {
     /// `counter` is assumed to be in the past, no longer than a period old.
     fn at(&self, counter: u32) -> TimeSpan<T>;
 }
+
+#[cfg(test)]
+pub mod fakes {
+    use core::{
+        marker::PhantomData,
+        sync::atomic::{AtomicI64, Ordering},
+    };
+
+    use super::*;
+
+    /// An uptime that only advances when told to.
+    pub struct FakeUptime<T: Tick> {
+        now: AtomicI64,
+        tick: PhantomData<T>,
+    }
+
+    impl<T: Tick> FakeUptime<T> {
+        pub fn new() -> Self {
+            Self {
+                now: AtomicI64::new(0),
+                tick: PhantomData,
+            }
+        }
+
+        pub fn set(&self, now: TimeSpan<T>) {
+            self.now.store(now.0, Ordering::Relaxed);
+        }
+    }
+
+    impl<T: Tick> Uptime<T> for FakeUptime<T> {
+        fn counter(&self) -> u32 {
+            self.now.load(Ordering::Relaxed) as u32
+        }
+
+        fn now(&self) -> TimeSpan<T> {
+            TimeSpan::from_ticks(self.now.load(Ordering::Relaxed))
+        }
+
+        fn at(&self, counter: u32) -> TimeSpan<T> {
+            let now = self.now.load(Ordering::Relaxed);
+            TimeSpan::from_ticks(now - (now as u32).wrapping_sub(counter) as i64)
+        }
+    }
+}
@@ -3,7 +3,7 @@ use core::{
     marker::PhantomData,
     sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
 };
-use drone_core::{fib, thr::prelude::*, thr::ThrToken};
+use drone_core::{fib, sync::Mutex, thr::prelude::*, thr::ThrToken};
 
 use crate::{Tick, TimeSpan, Uptime, UptimeCounter, UptimeOverflow};
 
@@ -13,11 +13,24 @@ pub struct UptimeDrv<T: Tick, Cnt: UptimeCounter<T, A>, Ovf: UptimeOverflow<A>,
     overflow: Ovf,
     /// The number of threads simultaneously calling now() and seeing the "pending overflow" flag.
     get_overflows_level: AtomicUsize,
-    /// The number of timer overflow interrupts that have occured.
+    /// The number of timer overflow interrupts that have occured, the low word.
     overflows: AtomicU32,
+    /// The high word of the number of overflows in bits 31..1,
+    /// and the most significant bit of `overflows` when it was last updated in bit 0.
+    overflows_hi: AtomicU32,
     /// The next value to use for `overflows`.
     overflows_next: AtomicU32,
     overflows_next_pending: AtomicBool,
+    /// Incremented each time a pending overflow is handled, so that readers can detect a handled overflow.
+    overflows_seq: AtomicU32,
+    /// The uptime and the reference time at the last check for missed overflows.
+    last_check: Mutex<Option<(TimeSpan<T>, TimeSpan<T>)>>,
+    /// The number of overflows that were missed.
+    missed: AtomicU32,
+    /// Whether missed overflows are added to the time.
+    correct_missed: AtomicBool,
+    /// The number of missed overflows that are added to the time.
+    corrected: AtomicU32,
     adapter: PhantomData<A>,
 }
 
@@ -41,8 +54,14 @@ where
             overflow,
             get_overflows_level: AtomicUsize::new(0),
             overflows: AtomicU32::new(0),
+            overflows_hi: AtomicU32::new(0),
             overflows_next: AtomicU32::new(1),
             overflows_next_pending: AtomicBool::new(false),
+            overflows_seq: AtomicU32::new(0),
+            last_check: Mutex::new(None),
+            missed: AtomicU32::new(0),
+            correct_missed: AtomicBool::new(false),
+            corrected: AtomicU32::new(0),
             adapter: PhantomData,
         });
 
@@ -64,24 +83,128 @@ where
         uptime
     }
 
-    fn sample(&self) -> (u32, u32) {
+    /// Cross-check the uptime against `reference`, and get the number of overflows missed since the last check,
+    /// because the overflow interrupt was blocked for more than a timer period.
+    ///
+    /// The reference must not be blocked by the same, e.g. a slower low power timer or the SysTick,
+    /// and must be accurate to within half a timer period between two checks.
+    /// This must be called more often than the reference wraps.
+    pub fn check_missed<RT: Tick>(&self, reference: &dyn Uptime<RT>) -> u32 {
+        let mut last = match self.last_check.try_lock() {
+            Some(last) => last,
+            // Another thread is checking.
+            None => return 0,
+        };
+
+        let reference = reference.now();
+        let reference = TimeSpan::<T>::from_ticks(
+            (reference.0 as i128 * T::FREQ as i128 / RT::FREQ as i128) as i64,
+        );
+        let mut uptime = self.now();
+        let missed = match *last {
+            Some((last_uptime, last_reference)) => {
+                let lost = (reference - last_reference) - (uptime - last_uptime);
+                let period = Ovf::PERIOD as i64;
+                ((lost.0 + period / 2) / period).max(0) as u32
+            }
+            None => 0,
+        };
+
+        if missed > 0 {
+            self.missed.fetch_add(missed, Ordering::Relaxed);
+            if self.correct_missed.load(Ordering::Relaxed) {
+                self.corrected.fetch_add(missed, Ordering::Release);
+                // The corrected periods are not lost at the next check.
+                uptime += TimeSpan::from_ticks(missed as i64 * Ovf::PERIOD as i64);
+            }
+        }
+
+        *last = Some((uptime, reference));
+        missed
+    }
+
+    /// Get the total number of missed overflows.
+    pub fn missed(&self) -> u32 {
+        self.missed.load(Ordering::Relaxed)
+    }
+
+    /// Add the overflows that are missed from now on to the time.
+    pub fn set_correct_missed(&self, correct: bool) {
+        self.correct_missed.store(correct, Ordering::Relaxed);
+    }
+
+    /// Get the overflows since start, including the corrected missed overflows.
+    fn total_overflows(&self, overflows: u64) -> u64 {
+        overflows + self.corrected.load(Ordering::Acquire) as u64
+    }
+
+    fn sample(&self) -> (u64, u32) {
+        // The fast path does not take part in the overflow handling of get_overflows(),
+        // its only write is the lock-free update of the high word in extend_overflows().
+        // It is valid if there is no pending overflow before or after the counter is read,
+        // and if no overflow was handled by another thread in between.
+        let seq = self.overflows_seq.load(Ordering::Acquire);
+        if !self.overflow.is_pending_overflow() {
+            let hi = self.overflows_hi.load(Ordering::Acquire);
+            let overflows = self.overflows.load(Ordering::Acquire);
+            let counter = self.counter.value();
+            if !self.overflow.is_pending_overflow()
+                && self.overflows_seq.load(Ordering::Acquire) == seq
+            {
+                return (self.extend_overflows(hi, overflows), counter);
+            }
+        }
+
+        self.sample_slow()
+    }
+
+    fn sample_slow(&self) -> (u64, u32) {
         // Two things can happen while invoking now()
         // * Any other thread can interrupt and maybe call now()
         // * The underlying timer runs underneath and may wrap during the invocation
 
         loop {
+            // The high word must be read before the low word, so that the low word is never older than the high word.
+            let hi = self.overflows_hi.load(Ordering::Acquire);
             let cnt1 = self.counter.value();
             let overflows = self.get_overflows();
             let cnt2 = self.counter.value();
             if cnt1 <= cnt2 {
                 // There was no timer wrap while `overflows` was obtained.
-                break (overflows, cnt2);
+                break (self.extend_overflows(hi, overflows), cnt2);
             } else {
                 // The underlying timer wrapped, retry
             }
         }
     }
 
+    /// Extend the low word of the overflows to 64 bits, given the previously read high word `hi`.
+    fn extend_overflows(&self, hi: u32, overflows: u32) -> u64 {
+        let epoch = hi >> 1;
+        let epoch = match (hi & 1, overflows >> 31) {
+            (0, 1) => {
+                // The low word has passed half of its range since the high word was updated.
+                self.update_overflows_hi(hi, epoch << 1 | 1);
+                epoch
+            }
+            (1, 0) => {
+                // The low word has wrapped since the high word was updated.
+                let epoch = epoch.wrapping_add(1);
+                self.update_overflows_hi(hi, epoch << 1);
+                epoch
+            }
+            _ => epoch,
+        };
+        (epoch as u64) << 32 | overflows as u64
+    }
+
+    fn update_overflows_hi(&self, current: u32, new: u32) {
+        // Another thread may have done the same update already, in which case this has no effect.
+        self.overflows_hi
+            .compare_exchange(current, new, Ordering::Release, Ordering::Relaxed)
+            .ok();
+    }
+
     fn get_overflows(&self) -> u32 {
         // Increment the thread-recursion count, and get "our" level
         let level = self.get_overflows_level.fetch_add(1, Ordering::Acquire);
@@ -92,6 +215,7 @@ where
             self.overflows.store(overflows_next, Ordering::Relaxed);
 
             self.overflow.clear_pending_overflow();
+            self.overflows_seq.fetch_add(1, Ordering::Release);
 
             self.overflows_next_pending.store(true, Ordering::Release);
 
@@ -103,7 +227,8 @@ where
         if level == 0
             && self
                 .overflows_next_pending
-                .compare_exchange(true, false, Ordering::AcqRel, Ordering::Relaxed).is_ok()
+                .compare_exchange(true, false, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
         {
             // We are the outer-most thread (lowest priority) that have called now() and seen the overflow flag.
             // The flag is now cleared, and so there is a-lot of time until the pending flag is seen again,
@@ -134,13 +259,13 @@ where
     #[inline]
     fn now(&self) -> TimeSpan<T> {
         let (overflows, counter) = self.sample();
-        let ticks = overflows as u64 * Ovf::PERIOD + counter as u64;
+        let ticks = self.total_overflows(overflows) * Ovf::PERIOD + counter as u64;
         TimeSpan::from_ticks(ticks as i64)
     }
 
     fn at(&self, counter: u32) -> TimeSpan<T> {
         let sample = self.sample();
-        let ticks = sample.0 as u64 * Ovf::PERIOD + sample.1 as u64;
+        let ticks = self.total_overflows(sample.0) * Ovf::PERIOD + sample.1 as u64;
         let now = TimeSpan::from_ticks(ticks as i64);
         let delta = if counter <= sample.1 {
             (sample.1 - counter) as i64
@@ -150,3 +275,264 @@ where
         now - TimeSpan::from_ticks(delta)
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    #[cfg(feature = "std")]
+    use alloc::rc::Rc;
+    #[cfg(feature = "std")]
+    use core::cell::RefCell;
+    use core::sync::atomic::AtomicU64;
+    use drone_core::{thr, token::Token};
+
+    use crate::uptime::fakes::FakeUptime;
+
+    use super::*;
+
+    struct Adapter;
+
+    struct TestTick;
+
+    impl Tick for TestTick {
+        const FREQ: u32 = 32768;
+    }
+
+    const MAX: u32 = 0xF;
+
+    #[cfg(feature = "std")]
+    std::thread_local! {
+        /// Called when a hardware access is preempted.
+        static PREEMPT: RefCell<Option<Rc<dyn Fn()>>> = RefCell::new(None);
+    }
+
+    struct FakeTimer {
+        counter: AtomicU32,
+        pending: AtomicBool,
+        /// The actual number of overflows.
+        overflows: AtomicU64,
+        /// The number of hardware accesses.
+        accesses: AtomicU32,
+        /// A mask of the hardware accesses to preempt.
+        preempt: AtomicU64,
+    }
+
+    impl FakeTimer {
+        fn new(counter: u32, overflows: u64) -> Arc<Self> {
+            Arc::new(Self {
+                counter: AtomicU32::new(counter),
+                pending: AtomicBool::new(false),
+                overflows: AtomicU64::new(overflows),
+                accesses: AtomicU32::new(0),
+                preempt: AtomicU64::new(0),
+            })
+        }
+
+        fn ticks(&self) -> u64 {
+            self.overflows.load(Ordering::Relaxed) * (MAX as u64 + 1)
+                + self.counter.load(Ordering::Relaxed) as u64
+        }
+
+        fn advance(&self, ticks: u32) {
+            let counter = self.counter.load(Ordering::Relaxed) + ticks;
+            if counter > MAX {
+                self.overflows.fetch_add(1, Ordering::Relaxed);
+                self.pending.store(true, Ordering::Relaxed);
+            }
+            self.counter.store(counter % (MAX + 1), Ordering::Relaxed);
+        }
+
+        /// Preempt the access if it is selected to be preempted.
+        fn access(&self) {
+            let access = self.accesses.fetch_add(1, Ordering::Relaxed);
+            if access < 64 && self.preempt.load(Ordering::Relaxed) & 1 << access != 0 {
+                preempt();
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn preempt() {
+        if let Some(preempt) = PREEMPT.with(|p| p.borrow().clone()) {
+            preempt();
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn preempt() {}
+
+    struct FakeCounter(Arc<FakeTimer>);
+
+    struct FakeOverflow(Arc<FakeTimer>);
+
+    impl UptimeCounter<TestTick, Adapter> for FakeCounter {
+        fn value(&self) -> u32 {
+            self.0.access();
+            self.0.counter.load(Ordering::Relaxed)
+        }
+    }
+
+    impl UptimeOverflow<Adapter> for FakeOverflow {
+        const MAX: u32 = MAX;
+
+        fn overflow_int_enable(&self) {}
+
+        fn is_pending_overflow(&self) -> bool {
+            self.0.access();
+            self.0.pending.load(Ordering::Relaxed)
+        }
+
+        fn clear_pending_overflow(&self) {
+            self.0.access();
+            self.0.pending.store(false, Ordering::Relaxed);
+        }
+    }
+
+    type FakeUptimeDrv = UptimeDrv<TestTick, FakeCounter, FakeOverflow, Adapter>;
+
+    thr::pool! {
+        thread => Thr {};
+        local => ThrLocal {};
+        index => Thrs;
+        threads => { thr0 };
+    }
+
+    fn create(timer: &Arc<FakeTimer>, overflows: u64) -> Arc<FakeUptimeDrv> {
+        let thread = unsafe { Thr0::take() };
+        let uptime = UptimeDrv::new(
+            FakeCounter(timer.clone()),
+            FakeOverflow(timer.clone()),
+            thread,
+            TestTick,
+        );
+        uptime.overflows.store(overflows as u32, Ordering::Relaxed);
+        uptime
+            .overflows_next
+            .store((overflows as u32).wrapping_add(1), Ordering::Relaxed);
+        uptime.overflows_hi.store(
+            ((overflows >> 32) as u32) << 1 | (overflows as u32) >> 31,
+            Ordering::Relaxed,
+        );
+        uptime
+    }
+
+    /// Get `now()` and assert that it is within the actual time before and after the call.
+    #[cfg(feature = "std")]
+    fn now_within(timer: &FakeTimer, uptime: &FakeUptimeDrv) -> u64 {
+        let before = timer.ticks();
+        let now = uptime.now().0 as u64;
+        let after = timer.ticks();
+        assert!(
+            before <= now && now <= after,
+            "{} <= {} <= {}",
+            before,
+            now,
+            after
+        );
+        now
+    }
+
+    #[test]
+    fn overflows_beyond_u32() {
+        let timer = FakeTimer::new(0x4, u32::MAX as u64 - 1);
+        let uptime = create(&timer, u32::MAX as u64 - 1);
+
+        let mut last = uptime.now();
+        for overflows in [u32::MAX as u64, 1 << 32, (1 << 32) + 1] {
+            timer.advance(MAX + 1);
+            let now = uptime.now();
+            assert_eq!(overflows * (MAX as u64 + 1) + 0x4, now.0 as u64);
+            assert_eq!(timer.ticks(), now.0 as u64);
+            assert!(now > last);
+            last = now;
+        }
+
+        // The high word is only updated when the low word changes half.
+        assert_eq!(1 << 1, uptime.overflows_hi.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn detect_missed() {
+        let timer = FakeTimer::new(0, 0);
+        let uptime = create(&timer, 0);
+        let reference = FakeUptime::<TestTick>::new();
+
+        assert_eq!(0, uptime.check_missed(&reference));
+
+        // Two of five periods were lost.
+        for _ in 0..3 {
+            timer.advance(MAX + 1);
+            uptime.now();
+        }
+        reference.set(TimeSpan::from_ticks(5 * (MAX as i64 + 1)));
+        assert_eq!(2, uptime.check_missed(&reference));
+        assert_eq!(2, uptime.missed());
+
+        // The missed periods are not corrected.
+        assert_eq!(3 * (MAX as i64 + 1), uptime.now().0);
+    }
+
+    #[test]
+    fn correct_missed() {
+        let timer = FakeTimer::new(0, 0);
+        let uptime = create(&timer, 0);
+        let reference = FakeUptime::<TestTick>::new();
+        uptime.set_correct_missed(true);
+
+        assert_eq!(0, uptime.check_missed(&reference));
+
+        timer.advance(MAX + 1);
+        uptime.now();
+        reference.set(TimeSpan::from_ticks(2 * (MAX as i64 + 1)));
+        assert_eq!(1, uptime.check_missed(&reference));
+        assert_eq!(2 * (MAX as i64 + 1), uptime.now().0);
+
+        // The corrected period is not detected again.
+        timer.advance(MAX + 1);
+        uptime.now();
+        reference.set(TimeSpan::from_ticks(3 * (MAX as i64 + 1)));
+        assert_eq!(0, uptime.check_missed(&reference));
+        assert_eq!(3 * (MAX as i64 + 1), uptime.now().0);
+        assert_eq!(1, uptime.missed());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn now_is_monotonic_when_preempted() {
+        // Preempt any two hardware accesses, either by letting time pass, or by letting time pass and calling now().
+        for overflows in [(1 << 31) - 1, (1 << 32) - 1] {
+            for counter in [MAX - 1, MAX] {
+                for first in 0..16 {
+                    for second in first + 1..16 {
+                        for nested in 0..4 {
+                            let timer = FakeTimer::new(counter, overflows);
+                            let uptime = create(&timer, overflows);
+
+                            let count = Rc::new(AtomicU32::new(0));
+                            let preempt = {
+                                let timer = timer.clone();
+                                let uptime = uptime.clone();
+                                let count = count.clone();
+                                move || {
+                                    timer.advance(1);
+                                    if nested & 1 << count.fetch_add(1, Ordering::Relaxed) != 0 {
+                                        now_within(&timer, &uptime);
+                                    }
+                                }
+                            };
+                            PREEMPT.with(|p| p.replace(Some(Rc::new(preempt))));
+                            timer
+                                .preempt
+                                .store(1 << first | 1 << second, Ordering::Relaxed);
+
+                            let now = now_within(&timer, &uptime);
+                            timer.preempt.store(0, Ordering::Relaxed);
+                            assert!(now_within(&timer, &uptime) >= now);
+
+                            PREEMPT.with(|p| p.replace(None));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,30 +1,619 @@
-use alloc::sync::Arc;
+use alloc::{boxed::Box, sync::Arc};
+use atomicbox::AtomicOptionBox;
+use core::{
+    cell::UnsafeCell,
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    ptr,
+    sync::atomic::{fence, AtomicBool, AtomicU32, AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+use futures::{future, prelude::*};
 
-use crate::{DateTime, Tick, TimeSpan, Uptime};
+use crate::{
+    latch::Latch,
+    listeners::{Listener, Listeners},
+    persist::{RestoreError, State, WATCH_STATE_SIZE},
+    Alarm, DateTime, Tick, TimeQuality, TimeSample, TimeSourceKind, TimeSpan, Uptime,
+};
 
 struct Adjust<T: Tick> {
-    datetime: DateTime,
+    /// The upstamp at which the adjustment was made.
     upstamp: TimeSpan<T>,
+    /// The wall clock time at `upstamp`, as the time since the unix epoch.
+    wall: TimeSpan<T>,
+    /// The correction that is slewed in after `upstamp`.
+    slew: TimeSpan<T>,
+    /// The estimated frequency error of the uptime in parts per billion.
+    drift_ppb: i32,
+    /// The number of times the watch is set.
+    revision: u32,
+    /// The sum of all corrections made since the watch was first set.
+    correction: TimeSpan<T>,
+    /// The source of the sample that the adjustment is based on.
+    source: TimeSourceKind,
+    /// The upstamp of the sample that the adjustment is based on.
+    synced: TimeSpan<T>,
+    /// The maximum error of the sample that the adjustment is based on.
+    error: TimeSpan<T>,
+}
+
+impl<T: Tick> Copy for Adjust<T> {}
+
+impl<T: Tick> Clone for Adjust<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Tick> Adjust<T> {
+    /// Get the wall clock time at `upstamp`.
+    fn wall_at(&self, upstamp: TimeSpan<T>, max_rate_ppm: u32) -> TimeSpan<T> {
+        let elapsed = upstamp - self.upstamp;
+        self.wall + compensate(elapsed, self.drift_ppb) + self.slewed(elapsed, max_rate_ppm)
+    }
+
+    /// Get the part of the slew correction that is applied `elapsed` after the adjustment.
+    fn slewed(&self, elapsed: TimeSpan<T>, max_rate_ppm: u32) -> TimeSpan<T> {
+        if elapsed <= TimeSpan::ZERO || self.slew == TimeSpan::ZERO {
+            return TimeSpan::ZERO;
+        }
+
+        let applied = elapsed.0 as i128 * max_rate_ppm as i128 / 1_000_000;
+        if applied >= self.slew.0.abs() as i128 {
+            self.slew
+        } else {
+            TimeSpan::from_ticks(applied as i64 * self.slew.0.signum())
+        }
+    }
+}
+
+/// The most recent adjustments, readable from any thread without waiting for the writer.
+///
+/// Adjustments are pushed in the order they are made, which need not be the order of their upstamps.
+/// An adjustment supersedes all earlier ones from its upstamp on, so those with a later upstamp are skipped,
+/// which leaves the adjustments in use sorted by upstamp.
+struct History<T: Tick, const N: usize> {
+    /// The sequence number, odd while an adjustment is pushed.
+    seq: AtomicU32,
+    /// A ring of adjustments, `None` where no adjustment is made yet.
+    adjusts: [UnsafeCell<Option<Adjust<T>>>; N],
+    /// The number of adjustments pushed, kept below `2 * N` once the ring is full.
+    pushes: AtomicUsize,
+}
+
+unsafe impl<T: Tick, const N: usize> Sync for History<T, N> {}
+
+impl<T: Tick, const N: usize> History<T, N> {
+    fn new() -> Self {
+        assert!(N > 0, "The history must hold at least one adjustment.");
+        Self {
+            seq: AtomicU32::new(0),
+            adjusts: [(); N].map(|_| UnsafeCell::new(None)),
+            pushes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Add an adjustment, replacing the oldest one if the history is full.
+    ///
+    /// # Safety
+    ///
+    /// There must never be more than one thread pushing at the same time.
+    unsafe fn push(&self, adjust: Adjust<T>) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        // Only the slot of the oldest adjustment is written, readers that preempt us skip it.
+        let pushes = self.pushes.load(Ordering::Relaxed);
+        ptr::write_volatile(self.adjusts[pushes % N].get(), Some(adjust));
+        let pushes = if pushes + 1 < 2 * N {
+            pushes + 1
+        } else {
+            pushes + 1 - N
+        };
+        self.pushes.store(pushes, Ordering::Relaxed);
+
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    /// Get the adjustment that was in effect at `upstamp`.
+    /// The adjustment with the earliest upstamp is used for an upstamp before all adjustments in the history.
+    fn at(&self, upstamp: TimeSpan<T>) -> Option<Adjust<T>> {
+        'retry: loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            let pushes = self.pushes.load(Ordering::Relaxed);
+            let len = if seq & 1 == 0 {
+                pushes.min(N)
+            } else {
+                // The oldest adjustment is being replaced.
+                pushes.min(N - 1)
+            };
+
+            let mut found: Option<Adjust<T>> = None;
+            for age in 0..len {
+                let adjust =
+                    unsafe { ptr::read_volatile(self.adjusts[(pushes - 1 - age) % N].get()) };
+                fence(Ordering::Acquire);
+                if self.seq.load(Ordering::Relaxed) != seq {
+                    // The writer preempted us and may have modified the adjustment while it was read, retry.
+                    continue 'retry;
+                }
+
+                let adjust = match adjust {
+                    Some(adjust) => adjust,
+                    None => break,
+                };
+                if matches!(found, Some(newer) if adjust.upstamp >= newer.upstamp) {
+                    // Superseded by a newer adjustment.
+                    continue;
+                }
+                found = Some(adjust);
+                if adjust.upstamp <= upstamp {
+                    break;
+                }
+            }
+            break found;
+        }
+    }
+}
+
+/// The state of the drift estimation, only accessed by the writer.
+struct Estimator<T: Tick> {
+    /// The sample that the next drift measurement is made against.
+    anchor: Option<Anchor<T>>,
+    /// The current estimate in parts per billion, `None` if nothing is measured yet.
+    drift_ppb: Option<i32>,
+}
+
+struct Anchor<T: Tick> {
+    upstamp: TimeSpan<T>,
+    wall: TimeSpan<T>,
+}
+
+impl<T: Tick> Copy for Estimator<T> {}
+
+impl<T: Tick> Clone for Estimator<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Tick> Copy for Anchor<T> {}
+
+impl<T: Tick> Clone for Anchor<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+/// The configuration of a slewing watch.
+struct Slew<T: Tick> {
+    /// The maximum rate at which corrections are slewed in.
+    max_rate_ppm: u32,
+    /// Corrections larger than this are stepped.
+    step_threshold: TimeSpan<T>,
 }
 
 #[derive(Debug)]
 pub struct NotSetError;
 
-pub struct Watch<U: Uptime<T>, T: Tick> {
+/// A change of a `Watch`.
+pub enum WatchEvent<T: Tick> {
+    /// The watch was set for the first time.
+    FirstSet,
+    /// The watch was set, and the time is corrected by `delta`.
+    /// A correction that is slewed in is reported in full when it is started.
+    Adjusted { delta: TimeSpan<T> },
+}
+
+/// A stream of the changes of a `Watch`.
+///
+/// Changes made while the stream is not polled are combined into a single event.
+pub struct WatchEvents<'a, U: Uptime<T>, T: Tick, const N: usize = 1> {
+    watch: &'a Watch<U, T, N>,
+    listener: Listener<'a>,
+    /// The revision and total correction of the last reported change.
+    seen: Option<(u32, TimeSpan<T>)>,
+}
+
+/// A wall clock that can be shared between threads.
+///
+/// Readers never wait for a writer, so the watch can be read from any interrupt priority,
+/// and adjusted from any thread through a shared reference.
+///
+/// By default, every synchronization steps the time.
+/// A watch created `with_slew()` instead slews small corrections in over time,
+/// so that the time never jumps while it is corrected.
+///
+/// The frequency error of the uptime is estimated from successive synchronizations,
+/// and compensated for when the time is read.
+///
+/// The watch keeps the `N` most recent adjustments, so that an upstamp from before the last adjustment
+/// is converted with the adjustment that was in effect at the time.
+pub struct Watch<U: Uptime<T>, T: Tick, const N: usize = 1> {
     uptime: Arc<U>,
-    adjust: Option<Adjust<T>>,
+    slew: Option<Slew<T>>,
+    /// The minimum time between two samples that the drift is measured over.
+    drift_min_interval: TimeSpan<T>,
+    /// Measurements larger than this are rejected as outliers.
+    drift_max_ppb: i32,
+    /// The maximum change of the estimate for each measurement.
+    drift_max_step_ppb: i32,
+    /// The maximum frequency error of the uptime when not compensated for.
+    tolerance_ppm: u32,
+    /// The time after the last synchronization that the time is trusted.
+    holdover: TimeSpan<T>,
+    estimator: Latch<Estimator<T>>,
+    /// The current adjustment, `None` until the watch is set.
+    adjust: Latch<Option<Adjust<T>>>,
+    /// The current and past adjustments, only read to convert upstamps from before the current adjustment.
+    history: History<T, N>,
+    /// Whether a thread is currently writing `adjust`.
+    writing: AtomicBool,
+    /// The most recent sample that is not yet written, together with its ticket and a drift estimate to start from.
+    pending: AtomicOptionBox<(u32, TimeSample<T>, Option<i32>)>,
+    /// The ticket of the most recent call to `sync()`.
+    tickets: AtomicU32,
+    /// The ticket of the sample that `adjust` is based on.
+    written: AtomicU32,
+    /// The listeners to wake when `adjust` is written.
+    listeners: Listeners,
 }
 
 impl<U: Uptime<T>, T: Tick> Watch<U, T> {
     pub fn new(uptime: Arc<U>) -> Self {
+        Self::with_history(uptime)
+    }
+}
+
+impl<U: Uptime<T>, T: Tick, const N: usize> Watch<U, T, N> {
+    const DRIFT_MIN_INTERVAL: TimeSpan<T> = TimeSpan::from_mins(10);
+    const DRIFT_MAX_PPM: u32 = 500;
+    const DRIFT_MAX_STEP_PPM: u32 = 10;
+    const TOLERANCE_PPM: u32 = 50;
+
+    /// Create a new `Watch` that keeps the `N` most recent adjustments.
+    pub fn with_history(uptime: Arc<U>) -> Self {
         Self {
             uptime,
-            adjust: None,
+            slew: None,
+            drift_min_interval: Self::DRIFT_MIN_INTERVAL,
+            drift_max_ppb: Self::DRIFT_MAX_PPM as i32 * 1000,
+            drift_max_step_ppb: Self::DRIFT_MAX_STEP_PPM as i32 * 1000,
+            tolerance_ppm: Self::TOLERANCE_PPM,
+            holdover: TimeSpan::MAX,
+            estimator: Latch::new(Estimator {
+                anchor: None,
+                drift_ppb: None,
+            }),
+            adjust: Latch::new(None),
+            history: History::new(),
+            writing: AtomicBool::new(false),
+            pending: AtomicOptionBox::new(None),
+            tickets: AtomicU32::new(0),
+            written: AtomicU32::new(0),
+            listeners: Listeners::new(),
+        }
+    }
+
+    /// Slew corrections in at `max_rate_ppm`, and only step the time
+    /// when the correction is larger than `step_threshold`.
+    pub fn with_slew(mut self, max_rate_ppm: u32, step_threshold: TimeSpan<T>) -> Self {
+        self.slew = Some(Slew {
+            max_rate_ppm,
+            step_threshold,
+        });
+        self
+    }
+
+    /// Only measure the drift over samples at least `min_interval` apart,
+    /// reject measurements above `max_ppm`, and change the estimate by at most `max_step_ppm` for each measurement.
+    /// The first measurement is taken as the estimate as is.
+    pub fn with_drift_limits(
+        mut self,
+        min_interval: TimeSpan<T>,
+        max_ppm: u32,
+        max_step_ppm: u32,
+    ) -> Self {
+        self.drift_min_interval = min_interval;
+        self.drift_max_ppb = max_ppm as i32 * 1000;
+        self.drift_max_step_ppb = max_step_ppm as i32 * 1000;
+        self
+    }
+
+    /// Let the error bound of the time grow by `tolerance_ppm` of the time since the last synchronization,
+    /// and only trust the time for `holdover` after the last synchronization.
+    pub fn with_holdover(mut self, tolerance_ppm: u32, holdover: TimeSpan<T>) -> Self {
+        self.tolerance_ppm = tolerance_ppm;
+        self.holdover = holdover;
+        self
+    }
+
+    /// Set the time by hand.
+    ///
+    /// The watch may be set from any thread of a single core processor.
+    /// If this preempts another thread that is setting the watch, the time is set by that thread
+    /// when it resumes, i.e. only after this returns.
+    pub fn set(&self, datetime: DateTime, upstamp: TimeSpan<T>) {
+        self.sync(TimeSample {
+            datetime,
+            upstamp,
+            error: TimeSpan::ZERO,
+            source: TimeSourceKind::Manual,
+        });
+    }
+
+    /// Synchronize the time with a sample from a time source.
+    ///
+    /// Like `set()`, this relies on the threads that synchronize the watch running on a single core.
+    pub fn sync(&self, sample: TimeSample<T>) {
+        self.write(sample, None);
+    }
+
+    /// Get the state of the watch to persist across a reset, e.g. in backup SRAM.
+    ///
+    /// `retained` is the current reading of a time source that survives the reset, e.g. the RTC.
+    pub fn persist(&self, retained: DateTime) -> Result<[u8; WATCH_STATE_SIZE], NotSetError> {
+        let adjust = self.adjust.read().ok_or(NotSetError)?;
+        let state = State {
+            freq: T::FREQ,
+            wall: adjust.wall_at(self.uptime.now(), self.max_rate_ppm()).0,
+            retained,
+            drift_ppb: self.estimator.read().drift_ppb,
+            source: adjust.source,
+        };
+        Ok(state.encode())
+    }
+
+    /// Restore the watch from a persisted `state`.
+    ///
+    /// The time is re-anchored by the time that passed on the retained time source,
+    /// which read `retained` at `upstamp`.
+    pub fn restore(
+        &self,
+        state: &[u8; WATCH_STATE_SIZE],
+        retained: DateTime,
+        upstamp: TimeSpan<T>,
+    ) -> Result<(), RestoreError> {
+        let state = State::decode(state)?;
+        if state.freq != T::FREQ {
+            return Err(RestoreError::FrequencyMismatch);
         }
+
+        let wall = TimeSpan::from_ticks(state.wall) + (to_wall(retained) - to_wall(state.retained));
+        let datetime = from_wall(wall);
+        let sample = TimeSample {
+            datetime,
+            upstamp: upstamp - (wall - to_wall(datetime)),
+            // The retained time source is only read with a resolution of a second.
+            error: TimeSpan::from_secs(1),
+            source: state.source,
+        };
+        self.write(sample, state.drift_ppb);
+        Ok(())
     }
 
-    pub fn set(&mut self, datetime: DateTime, upstamp: TimeSpan<T>) {
-        self.adjust = Some(Adjust { datetime, upstamp });
+    /// Write `sample`, starting the drift estimation from `drift_ppb` if any.
+    fn write(&self, sample: TimeSample<T>, drift_ppb: Option<i32>) {
+        let ticket = self.tickets.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+        self.pending.store(
+            Some(Box::new((ticket, sample, drift_ppb))),
+            Ordering::AcqRel,
+        );
+
+        self.write_pending();
+    }
+
+    /// Write the pending samples, unless another writer is preempted by us.
+    ///
+    /// The preempted writer writes our sample before it returns, which it can only do
+    /// if it resumes after we return, i.e. when all writers run on the same core.
+    fn write_pending(&self) {
+        while let Some(pending) = self.pending.take(Ordering::AcqRel) {
+            if self
+                .writing
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                // We have preempted another writer.
+                // Leave the sample for it to write when it is done.
+                // If this fails, an even newer sample was left since we took ours, so ours is obsolete.
+                let _ = self.pending.try_store(pending, Ordering::AcqRel);
+                break;
+            }
+
+            let (ticket, sample, drift_ppb) = *pending;
+
+            // Never replace an adjustment with one from an earlier call that got delayed by preemption.
+            let written = self.written.load(Ordering::Relaxed);
+            if ticket.wrapping_sub(written) as i32 > 0 {
+                if drift_ppb.is_some() {
+                    unsafe {
+                        self.estimator.write(Estimator {
+                            anchor: None,
+                            drift_ppb,
+                        })
+                    };
+                }
+
+                let adjust = self.adjust(sample);
+                // The history is written first, so that it holds any adjustment that `adjust` holds.
+                unsafe { self.history.push(adjust) };
+                unsafe { self.adjust.write(Some(adjust)) };
+                self.written.store(ticket, Ordering::Relaxed);
+                self.listeners.wake_all();
+            }
+
+            self.writing.store(false, Ordering::Release);
+        }
+    }
+
+    /// Create the adjustment that corrects the current time according to `sample`.
+    fn adjust(&self, sample: TimeSample<T>) -> Adjust<T> {
+        let observed = to_wall(sample.datetime);
+        let drift_ppb = self.estimate_drift(sample.upstamp, observed);
+        let step = Adjust {
+            upstamp: sample.upstamp,
+            wall: observed,
+            slew: TimeSpan::ZERO,
+            drift_ppb,
+            revision: 1,
+            correction: TimeSpan::ZERO,
+            source: sample.source,
+            synced: sample.upstamp,
+            error: sample.error,
+        };
+
+        let current = match self.adjust.read() {
+            Some(current) => current,
+            None => return step,
+        };
+        let max_rate_ppm = self.max_rate_ppm();
+        let revision = current.revision.wrapping_add(1);
+
+        if let Some(slew) = &self.slew {
+            // Start slewing from the current time, so that the time continues from where it is now.
+            let mut upstamp = self.uptime.now();
+            if upstamp < sample.upstamp {
+                upstamp = sample.upstamp;
+            }
+            let wall = current.wall_at(upstamp, max_rate_ppm);
+            let offset = observed + compensate(upstamp - sample.upstamp, drift_ppb) - wall;
+
+            if offset.abs() <= slew.step_threshold {
+                return Adjust {
+                    upstamp,
+                    wall,
+                    slew: offset,
+                    drift_ppb,
+                    revision,
+                    correction: current.correction + offset,
+                    ..step
+                };
+            }
+        }
+
+        let delta = observed - current.wall_at(sample.upstamp, max_rate_ppm);
+        Adjust {
+            revision,
+            correction: current.correction + delta,
+            ..step
+        }
+    }
+
+    /// Update the drift estimate with the wall clock time `observed` at `upstamp`, and get the new estimate.
+    fn estimate_drift(&self, upstamp: TimeSpan<T>, observed: TimeSpan<T>) -> i32 {
+        let mut estimator = self.estimator.read();
+
+        match estimator.anchor {
+            Some(anchor)
+                if upstamp <= anchor.upstamp
+                    || upstamp - anchor.upstamp < self.drift_min_interval =>
+            {
+                // Too close to the anchor to give a meaningful measurement, keep measuring against the anchor.
+            }
+            anchor => {
+                if let Some(anchor) = anchor {
+                    let elapsed = upstamp - anchor.upstamp;
+                    let error = observed - anchor.wall - elapsed;
+                    let measured = error.0 as i128 * 1_000_000_000 / elapsed.0 as i128;
+
+                    if measured.abs() <= self.drift_max_ppb as i128 {
+                        let measured = measured as i32;
+                        estimator.drift_ppb = Some(match estimator.drift_ppb {
+                            Some(drift_ppb) => {
+                                drift_ppb
+                                    + (measured - drift_ppb)
+                                        .clamp(-self.drift_max_step_ppb, self.drift_max_step_ppb)
+                            }
+                            // Nothing is known about the drift before the first measurement,
+                            // so there is no estimate to limit the step from.
+                            None => measured,
+                        });
+                    } else {
+                        // An outlier, most likely the time source is off or the uptime was stopped.
+                    }
+                }
+
+                // Measure the next sample against this one, also after an outlier so that one bad sample is not repeated.
+                estimator.anchor = Some(Anchor {
+                    upstamp,
+                    wall: observed,
+                });
+                unsafe { self.estimator.write(estimator) };
+            }
+        }
+
+        estimator.drift_ppb.unwrap_or(0)
+    }
+
+    /// Get the estimated frequency error of the uptime in parts per million.
+    /// A positive value means that the uptime runs slow.
+    pub fn drift_ppm(&self) -> f32 {
+        self.adjust
+            .read()
+            .map_or(0.0, |adjust| adjust.drift_ppb as f32 / 1000.0)
+    }
+
+    /// Get a stream of the changes of the watch.
+    /// The first event is `FirstSet`, as soon as the watch is set.
+    pub fn events(&self) -> WatchEvents<'_, U, T, N> {
+        WatchEvents {
+            watch: self,
+            listener: self.listeners.listen(),
+            seen: None,
+        }
+    }
+
+    /// Get a future that completes with the next change of the watch.
+    pub fn changed(&self) -> impl Future<Output = WatchEvent<T>> + '_ {
+        let mut events = self.events();
+        events.seen = self
+            .adjust
+            .read()
+            .map(|adjust| (adjust.revision, adjust.correction));
+        async move { events.next().await.unwrap() }
+    }
+
+    /// Sleep until the wall clock reaches `datetime`.
+    ///
+    /// The remaining delay is recomputed whenever the watch is adjusted while sleeping,
+    /// and if the watch is not yet set, the sleep starts when it is.
+    pub async fn sleep_until<A: Alarm<T>>(&self, alarm: &A, datetime: DateTime) {
+        let deadline = to_wall(datetime);
+        loop {
+            // Create the future before the time is read, so that no adjustment is missed.
+            let changed = self.changed();
+            futures::pin_mut!(changed);
+
+            match self.adjust.read() {
+                Some(adjust) => match self.uptime_until(adjust, deadline) {
+                    Some(duration) => {
+                        future::select(alarm.sleep(duration), changed).await;
+                    }
+                    None => return,
+                },
+                None => {
+                    changed.await;
+                }
+            }
+        }
+    }
+
+    /// Get the uptime that at least passes before the wall clock reaches `deadline`, `None` if it is already reached.
+    fn uptime_until(&self, adjust: Adjust<T>, deadline: TimeSpan<T>) -> Option<TimeSpan<T>> {
+        let max_rate_ppm = self.max_rate_ppm();
+        let remaining = deadline - adjust.wall_at(self.uptime.now(), max_rate_ppm);
+        if remaining <= TimeSpan::ZERO {
+            return None;
+        }
+
+        // Neither the drift compensation nor the slewing makes the wall clock run faster than this.
+        let max_ppb = adjust.drift_ppb.max(0) as i128 + max_rate_ppm as i128 * 1000;
+        let ticks = remaining.0 as i128 * 1_000_000_000 / (1_000_000_000 + max_ppb);
+        Some(TimeSpan::from_ticks((ticks as i64).max(1)))
     }
 
     pub fn now(&self) -> Result<DateTime, NotSetError> {
@@ -32,20 +621,145 @@ impl<U: Uptime<T>, T: Tick> Watch<U, T> {
     }
 
     pub fn at(&self, upstamp: TimeSpan<T>) -> Result<DateTime, NotSetError> {
-        if let Some(adjust) = &self.adjust {
-            if upstamp > adjust.upstamp {
-                // upstamp was sampled after the time was last adjusted.
-                Ok(adjust.datetime + (upstamp - adjust.upstamp))
-            } else {
-                // upstamp was sampled before the time was last adjusted.
-                Ok(adjust.datetime - (adjust.upstamp - upstamp))
-            }
-        } else {
-            Err(NotSetError)
+        self.wall_at(upstamp).map(from_wall)
+    }
+
+    /// Get the current time together with its quality.
+    pub fn now_with_quality(&self) -> Result<(DateTime, TimeQuality<T>), NotSetError> {
+        self.at_with_quality(self.uptime.now())
+    }
+
+    /// Get the time at `upstamp` together with its quality.
+    pub fn at_with_quality(
+        &self,
+        upstamp: TimeSpan<T>,
+    ) -> Result<(DateTime, TimeQuality<T>), NotSetError> {
+        let adjust = self.adjust_at(upstamp).ok_or(NotSetError)?;
+        let max_rate_ppm = self.max_rate_ppm();
+        let wall = adjust.wall_at(upstamp, max_rate_ppm);
+
+        // The error grows with the time since the synchronization, and includes the part of the correction that is not yet slewed in.
+        let elapsed = (upstamp - adjust.synced).abs();
+        let unslewed = adjust.slew - adjust.slewed(upstamp - adjust.upstamp, max_rate_ppm);
+        let growth = elapsed.0 as i128 * self.tolerance_ppm as i128 / 1_000_000;
+        let error = adjust.error + unslewed.abs() + TimeSpan::from_ticks(growth as i64);
+
+        Ok((
+            from_wall(wall),
+            TimeQuality {
+                source: adjust.source,
+                synced: adjust.synced,
+                error,
+                trusted: elapsed <= self.holdover,
+            },
+        ))
+    }
+
+    /// Get the part of the last correction that is not yet slewed in.
+    pub fn remaining_correction(&self) -> Result<TimeSpan<T>, NotSetError> {
+        let adjust = self.adjust.read().ok_or(NotSetError)?;
+        let elapsed = self.uptime.now() - adjust.upstamp;
+        Ok(adjust.slew - adjust.slewed(elapsed, self.max_rate_ppm()))
+    }
+
+    /// Get the wall clock time at `upstamp`, as the time since the unix epoch.
+    fn wall_at(&self, upstamp: TimeSpan<T>) -> Result<TimeSpan<T>, NotSetError> {
+        let adjust = self.adjust_at(upstamp).ok_or(NotSetError)?;
+        Ok(adjust.wall_at(upstamp, self.max_rate_ppm()))
+    }
+
+    /// Get the adjustment that was in effect at `upstamp`.
+    fn adjust_at(&self, upstamp: TimeSpan<T>) -> Option<Adjust<T>> {
+        match self.adjust.read() {
+            Some(adjust) if adjust.upstamp <= upstamp => Some(adjust),
+            // The history is empty while a single adjustment history is being replaced.
+            Some(adjust) => self.history.at(upstamp).or(Some(adjust)),
+            None => None,
+        }
+    }
+
+    fn max_rate_ppm(&self) -> u32 {
+        self.slew.as_ref().map_or(0, |slew| slew.max_rate_ppm)
+    }
+}
+
+impl<U: Uptime<T>, T: Tick, const N: usize> WatchEvents<'_, U, T, N> {
+    /// Get the change since the last reported change, if any.
+    fn check(&mut self) -> Option<WatchEvent<T>> {
+        let adjust = self.watch.adjust.read()?;
+        let event = match self.seen {
+            None => WatchEvent::FirstSet,
+            Some((revision, _)) if revision == adjust.revision => return None,
+            Some((_, correction)) => WatchEvent::Adjusted {
+                delta: adjust.correction - correction,
+            },
+        };
+        self.seen = Some((adjust.revision, adjust.correction));
+        Some(event)
+    }
+}
+
+impl<U: Uptime<T>, T: Tick, const N: usize> Unpin for WatchEvents<'_, U, T, N> {}
+
+impl<U: Uptime<T>, T: Tick, const N: usize> Stream for WatchEvents<'_, U, T, N> {
+    type Item = WatchEvent<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let events = self.get_mut();
+        if let Some(event) = events.check() {
+            return Poll::Ready(Some(event));
+        }
+
+        events.listener.register(cx.waker());
+
+        // Check again in case the watch was set before the listener was registered.
+        match events.check() {
+            Some(event) => Poll::Ready(Some(event)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T: Tick> Copy for WatchEvent<T> {}
+
+impl<T: Tick> Clone for WatchEvent<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Tick> PartialEq for WatchEvent<T> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::FirstSet, Self::FirstSet) => true,
+            (Self::Adjusted { delta: a }, Self::Adjusted { delta: b }) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<T: Tick> Debug for WatchEvent<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::FirstSet => write!(f, "FirstSet"),
+            Self::Adjusted { delta } => f.debug_struct("Adjusted").field("delta", delta).finish(),
         }
     }
 }
 
+/// Get the wall clock time that passes during `elapsed` uptime, for an uptime with a frequency error of `drift_ppb`.
+fn compensate<T: Tick>(elapsed: TimeSpan<T>, drift_ppb: i32) -> TimeSpan<T> {
+    elapsed + TimeSpan::from_ticks((elapsed.0 as i128 * drift_ppb as i128 / 1_000_000_000) as i64)
+}
+
+pub(crate) fn to_wall<T: Tick>(datetime: DateTime) -> TimeSpan<T> {
+    TimeSpan::from_ticks(datetime.unixtimestamp() as i64 * T::FREQ as i64)
+}
+
+pub(crate) fn from_wall<T: Tick>(wall: TimeSpan<T>) -> DateTime {
+    DateTime::from_unixtimestamp(wall.0.div_euclid(T::FREQ as i64) as u32)
+}
+
 #[cfg(test)]
 pub mod tests {
     use drone_core::{
@@ -54,7 +768,11 @@ pub mod tests {
         token::Token,
     };
 
-    use crate::{Month, UptimeCounter, UptimeDrv, UptimeOverflow};
+    use crate::{
+        adapters::alarm::fakes::{FakeAlarmCounter, FakeAlarmTimer, FakeTick},
+        uptime::fakes::FakeUptime,
+        AlarmDrv, Month, UptimeCounter, UptimeDrv, UptimeOverflow,
+    };
 
     use super::*;
 
@@ -94,13 +812,52 @@ pub mod tests {
         threads => { thr0 };
     }
 
+    #[test]
+    fn set_shared() {
+        let counter = TestAlarm;
+        let overflow = TestAlarm;
+        let thread = unsafe { Thr0::take() };
+        let uptime = UptimeDrv::new(counter, overflow, thread, TestTick);
+        let watch = Arc::new(Watch::new(uptime));
+
+        let set_datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let set_upstamp = TimeSpan::from_secs(100);
+        let other = watch.clone();
+        other.set(set_datetime, set_upstamp);
+
+        assert_eq!(set_datetime, watch.at(set_upstamp).unwrap());
+        assert_eq!(set_datetime, other.at(set_upstamp).unwrap());
+    }
+
+    #[test]
+    fn set_preempting_writer() {
+        let counter = TestAlarm;
+        let overflow = TestAlarm;
+        let thread = unsafe { Thr0::take() };
+        let uptime = UptimeDrv::new(counter, overflow, thread, TestTick);
+        let watch = Watch::new(uptime);
+
+        let set_datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let set_upstamp = TimeSpan::from_secs(100);
+
+        // Pretend that we preempt another thread while it is writing.
+        watch.writing.store(true, Ordering::Relaxed);
+        watch.set(set_datetime, set_upstamp);
+        assert!(watch.at(set_upstamp).is_err());
+
+        // The preempted writer completes and writes the adjustment that was left for it.
+        watch.writing.store(false, Ordering::Relaxed);
+        watch.write_pending();
+        assert_eq!(set_datetime, watch.at(set_upstamp).unwrap());
+    }
+
     #[test]
     fn set() {
         let counter = TestAlarm;
         let overflow = TestAlarm;
         let thread = unsafe { Thr0::take() };
         let uptime = UptimeDrv::new(counter, overflow, thread, TestTick);
-        let mut watch = Watch::new(uptime);
+        let watch = Watch::new(uptime);
 
         let now = watch.now();
         assert!(now.is_err());
@@ -123,4 +880,425 @@ pub mod tests {
                 .unwrap()
         );
     }
+
+    fn slewing_watch() -> (
+        Arc<FakeUptime<TestTick>>,
+        Watch<FakeUptime<TestTick>, TestTick>,
+    ) {
+        let uptime = Arc::new(FakeUptime::new());
+        let watch = Watch::new(uptime.clone()).with_slew(500, TimeSpan::from_secs(2));
+        (uptime, watch)
+    }
+
+    #[test]
+    fn slew_small_correction() {
+        let (uptime, watch) = slewing_watch();
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        watch.set(datetime, TimeSpan::ZERO);
+
+        // The watch is one second behind.
+        uptime.set(TimeSpan::from_secs(100));
+        watch.set(
+            datetime + TimeSpan::<TestTick>::from_secs(101),
+            TimeSpan::from_secs(100),
+        );
+        assert_eq!(
+            datetime + TimeSpan::<TestTick>::from_secs(100),
+            watch.now().unwrap()
+        );
+        assert_eq!(
+            TimeSpan::from_secs(1),
+            watch.remaining_correction().unwrap()
+        );
+
+        // Half of the correction is slewed in after 1000 seconds at 500ppm.
+        uptime.set(TimeSpan::from_secs(1100));
+        assert_eq!(
+            TimeSpan::from_millis(500),
+            watch.remaining_correction().unwrap()
+        );
+        assert_eq!(
+            TimeSpan::from_millis(1_100_500),
+            watch.wall_at(uptime.now()).unwrap() - to_wall(datetime)
+        );
+
+        uptime.set(TimeSpan::from_secs(2100));
+        assert_eq!(TimeSpan::ZERO, watch.remaining_correction().unwrap());
+        assert_eq!(
+            datetime + TimeSpan::<TestTick>::from_secs(2101),
+            watch.now().unwrap()
+        );
+    }
+
+    #[test]
+    fn step_large_correction() {
+        let (uptime, watch) = slewing_watch();
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        watch.set(datetime, TimeSpan::ZERO);
+
+        uptime.set(TimeSpan::from_secs(100));
+        watch.set(
+            datetime + TimeSpan::<TestTick>::from_secs(90),
+            TimeSpan::from_secs(100),
+        );
+        assert_eq!(
+            datetime + TimeSpan::<TestTick>::from_secs(90),
+            watch.now().unwrap()
+        );
+        assert_eq!(TimeSpan::ZERO, watch.remaining_correction().unwrap());
+    }
+
+    #[test]
+    fn slew_backwards_is_monotonic() {
+        let (uptime, watch) = slewing_watch();
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        watch.set(datetime, TimeSpan::ZERO);
+
+        // The watch is one second ahead, and the sample is a while old.
+        uptime.set(TimeSpan::from_secs(100));
+        watch.set(
+            datetime + TimeSpan::<TestTick>::from_secs(49),
+            TimeSpan::from_secs(50),
+        );
+        assert_eq!(
+            TimeSpan::from_secs(-1),
+            watch.remaining_correction().unwrap()
+        );
+
+        let mut last = watch.wall_at(uptime.now()).unwrap();
+        for millis in (0..=2_100_000).step_by(100) {
+            let wall = watch
+                .wall_at(TimeSpan::from_secs(100) + TimeSpan::from_millis(millis))
+                .unwrap();
+            assert!(wall >= last);
+            last = wall;
+        }
+        assert_eq!(TimeSpan::from_secs(2199), last - to_wall(datetime));
+    }
+
+    /// Get the upstamp after `elapsed` wall clock time for an uptime that runs `ppb` slow.
+    fn slow(elapsed: TimeSpan<TestTick>, ppb: i64) -> TimeSpan<TestTick> {
+        TimeSpan::from_ticks(elapsed.0 - elapsed.0 * ppb / 1_000_000_000)
+    }
+
+    #[test]
+    fn estimate_drift() {
+        let uptime = Arc::new(FakeUptime::new());
+        let watch = Watch::new(uptime);
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let hour = TimeSpan::<TestTick>::from_hours(1);
+
+        watch.set(datetime, TimeSpan::ZERO);
+        assert_eq!(0.0, watch.drift_ppm());
+        watch.set(datetime + hour, slow(hour, 50_000));
+        assert!((watch.drift_ppm() - 50.0).abs() < 0.1);
+
+        // The drift is compensated for when the time is read.
+        let error =
+            watch.wall_at(slow(hour + hour, 50_000)).unwrap() - to_wall(datetime + hour + hour);
+        assert!(error.abs() < TimeSpan::from_millis(1));
+    }
+
+    #[test]
+    fn estimate_drift_limits() {
+        let uptime = Arc::new(FakeUptime::new());
+        let watch = Watch::new(uptime);
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let hour = TimeSpan::<TestTick>::from_hours(1);
+
+        watch.set(datetime, TimeSpan::ZERO);
+        let mut upstamp = slow(hour, 20_000);
+        watch.set(datetime + hour, upstamp);
+        assert!((watch.drift_ppm() - 20.0).abs() < 0.1);
+
+        // Samples too close together are not measured against each other.
+        watch.set(
+            datetime + hour + TimeSpan::<TestTick>::from_secs(1),
+            upstamp + TimeSpan::from_millis(900),
+        );
+        assert!((watch.drift_ppm() - 20.0).abs() < 0.1);
+
+        // Outliers are rejected.
+        upstamp += slow(hour, 800_000);
+        watch.set(datetime + hour + hour, upstamp);
+        assert!((watch.drift_ppm() - 20.0).abs() < 0.1);
+
+        // The estimate only moves by the maximum step.
+        upstamp += slow(hour, 100_000);
+        watch.set(datetime + hour + hour + hour, upstamp);
+        assert!((watch.drift_ppm() - 30.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn estimate_drift_same_upstamp() {
+        let uptime = Arc::new(FakeUptime::new());
+        let watch = Watch::new(uptime).with_drift_limits(TimeSpan::ZERO, 500, 10);
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let upstamp = TimeSpan::<TestTick>::from_hours(1);
+
+        // Samples with the same upstamp are not measured against each other.
+        watch.set(datetime, upstamp);
+        watch.set(datetime + TimeSpan::<TestTick>::from_millis(1), upstamp);
+        assert_eq!(0.0, watch.drift_ppm());
+    }
+
+    struct CountingWaker(AtomicU32);
+
+    impl alloc::task::Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn events() {
+        let uptime = Arc::new(FakeUptime::new());
+        let watch = Watch::new(uptime);
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let second = TimeSpan::<TestTick>::from_secs(1);
+
+        let counter = Arc::new(CountingWaker(AtomicU32::new(0)));
+        let waker = counter.clone().into();
+        let mut cx = Context::from_waker(&waker);
+        let mut events = watch.events();
+
+        assert!(events.poll_next_unpin(&mut cx).is_pending());
+        watch.set(datetime, TimeSpan::ZERO);
+        assert_eq!(1, counter.0.load(Ordering::Relaxed));
+        assert_eq!(
+            Poll::Ready(Some(WatchEvent::FirstSet)),
+            events.poll_next_unpin(&mut cx)
+        );
+
+        assert!(events.poll_next_unpin(&mut cx).is_pending());
+        assert!(events.poll_next_unpin(&mut cx).is_pending());
+        watch.set(datetime + second + second, second);
+        assert_eq!(2, counter.0.load(Ordering::Relaxed));
+
+        // Changes that are not yet reported are combined.
+        watch.set(datetime, second + second);
+        assert_eq!(2, counter.0.load(Ordering::Relaxed));
+        assert_eq!(
+            Poll::Ready(Some(WatchEvent::Adjusted {
+                delta: TimeSpan::ZERO - second - second
+            })),
+            events.poll_next_unpin(&mut cx)
+        );
+    }
+
+    #[test]
+    fn changed() {
+        let uptime = Arc::new(FakeUptime::<TestTick>::new());
+        let watch = Watch::new(uptime.clone()).with_slew(500, TimeSpan::from_secs(2));
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        watch.set(datetime, TimeSpan::ZERO);
+
+        let changed = watch.changed();
+        futures::pin_mut!(changed);
+        assert!(changed.as_mut().now_or_never().is_none());
+
+        uptime.set(TimeSpan::from_secs(100));
+        watch.set(
+            datetime + TimeSpan::<TestTick>::from_secs(101),
+            TimeSpan::from_secs(100),
+        );
+        assert_eq!(
+            Some(WatchEvent::Adjusted {
+                delta: TimeSpan::from_secs(1)
+            }),
+            changed.now_or_never()
+        );
+    }
+
+    #[test]
+    fn sleep_until_past() {
+        let uptime = Arc::new(FakeUptime::<FakeTick>::new());
+        let watch = Watch::new(uptime);
+        let alarm = AlarmDrv::new(
+            FakeAlarmCounter(0),
+            FakeAlarmTimer {
+                compares: Vec::new(),
+            },
+            FakeTick,
+        );
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        watch.set(datetime, TimeSpan::ZERO);
+
+        assert!(watch.sleep_until(&alarm, datetime).now_or_never().is_some());
+        assert!(watch
+            .sleep_until(&alarm, datetime - TimeSpan::<FakeTick>::from_secs(1))
+            .now_or_never()
+            .is_some());
+    }
+
+    #[test]
+    fn sleep_until_not_set() {
+        let uptime = Arc::new(FakeUptime::<FakeTick>::new());
+        let watch = Watch::new(uptime);
+        let alarm = AlarmDrv::new(
+            FakeAlarmCounter(0),
+            FakeAlarmTimer {
+                compares: Vec::new(),
+            },
+            FakeTick,
+        );
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+
+        let sleep = watch.sleep_until(&alarm, datetime);
+        futures::pin_mut!(sleep);
+        assert!(sleep.as_mut().now_or_never().is_none());
+
+        // The watch is set to a time after the deadline.
+        watch.set(
+            datetime + TimeSpan::<FakeTick>::from_secs(1),
+            TimeSpan::ZERO,
+        );
+        assert!(sleep.now_or_never().is_some());
+    }
+
+    #[test]
+    fn uptime_until() {
+        let uptime = Arc::new(FakeUptime::new());
+        let watch = Watch::new(uptime).with_slew(500, TimeSpan::from_secs(2));
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let hour = TimeSpan::<TestTick>::from_hours(1);
+        watch.set(datetime, TimeSpan::ZERO);
+
+        // Never sleep past the deadline while a correction is slewed in.
+        watch.set(
+            datetime + TimeSpan::<TestTick>::from_secs(1),
+            TimeSpan::ZERO,
+        );
+        let adjust = watch.adjust.read().unwrap();
+        let duration = watch
+            .uptime_until(adjust, to_wall(datetime) + hour)
+            .unwrap();
+        assert!(watch.wall_at(duration).unwrap() <= to_wall(datetime) + hour);
+        assert!(duration > hour - TimeSpan::from_secs(2));
+
+        assert!(watch.uptime_until(adjust, to_wall(datetime)).is_none());
+    }
+
+    #[test]
+    fn history() {
+        let uptime = Arc::new(FakeUptime::new());
+        // Reject all drift measurements to keep the arithmetic simple.
+        let watch: Watch<_, _, 2> =
+            Watch::with_history(uptime).with_drift_limits(TimeSpan::ZERO, 0, 0);
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let second = TimeSpan::<TestTick>::from_secs(1);
+        let hour = TimeSpan::<TestTick>::from_hours(1);
+
+        watch.set(datetime, TimeSpan::ZERO);
+        watch.set(datetime + hour + second, hour);
+
+        // Upstamps before the last adjustment use the adjustment in effect at the time.
+        assert_eq!(datetime + second, watch.at(second).unwrap());
+        assert_eq!(datetime + hour + second, watch.at(hour).unwrap());
+        assert_eq!(
+            datetime + hour + second + second,
+            watch.at(hour + second).unwrap()
+        );
+
+        // The oldest adjustment is replaced when the history is full.
+        watch.set(datetime + hour + hour + second + second, hour + hour);
+        assert_eq!(datetime + second + second, watch.at(second).unwrap());
+        assert_eq!(
+            datetime + hour + second + second,
+            watch.at(hour + second).unwrap()
+        );
+    }
+
+    #[test]
+    fn history_out_of_order() {
+        let uptime = Arc::new(FakeUptime::new());
+        let watch: Watch<_, _, 2> =
+            Watch::with_history(uptime).with_drift_limits(TimeSpan::ZERO, 0, 0);
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let second = TimeSpan::<TestTick>::from_secs(1);
+        let hour = TimeSpan::<TestTick>::from_hours(1);
+
+        // A delayed sample with an earlier upstamp supersedes the adjustment before it.
+        watch.set(datetime, TimeSpan::ZERO);
+        watch.set(datetime + hour + second, hour);
+        watch.set(datetime + TimeSpan::<TestTick>::from_secs(11), second);
+
+        let offset = TimeSpan::<TestTick>::from_secs(10);
+        assert_eq!(datetime + hour + offset, watch.at(hour).unwrap());
+        assert_eq!(datetime + second + offset, watch.at(second).unwrap());
+        // The superseded adjustment is not used for an upstamp before all others.
+        assert_eq!(datetime + offset, watch.at(TimeSpan::ZERO).unwrap());
+    }
+
+    #[test]
+    fn quality() {
+        let uptime = Arc::new(FakeUptime::new());
+        let watch = Watch::new(uptime.clone()).with_holdover(50, TimeSpan::from_hours(1));
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let hour = TimeSpan::<TestTick>::from_hours(1);
+
+        watch.sync(TimeSample {
+            datetime,
+            upstamp: TimeSpan::ZERO,
+            error: TimeSpan::from_millis(10),
+            source: TimeSourceKind::Gnss,
+        });
+
+        let (now, quality) = watch.now_with_quality().unwrap();
+        assert_eq!(datetime, now);
+        assert_eq!(TimeSourceKind::Gnss, quality.source);
+        assert_eq!(TimeSpan::ZERO, quality.synced);
+        assert_eq!(TimeSpan::from_millis(10), quality.error);
+        assert!(quality.trusted);
+
+        // The error grows by 50ppm, i.e. 180ms each hour.
+        let (_, quality) = watch.at_with_quality(hour).unwrap();
+        assert_eq!(TimeSpan::from_millis(190), quality.error);
+        assert!(quality.trusted);
+
+        // The holdover period has expired.
+        uptime.set(hour + TimeSpan::from_secs(1));
+        let (_, quality) = watch.now_with_quality().unwrap();
+        assert!(!quality.trusted);
+
+        // A manual set has no error.
+        watch.set(datetime, TimeSpan::from_hours(2));
+        let (_, quality) = watch.at_with_quality(TimeSpan::from_hours(2)).unwrap();
+        assert_eq!(TimeSourceKind::Manual, quality.source);
+        assert_eq!(TimeSpan::ZERO, quality.error);
+    }
+
+    #[test]
+    fn persist_restore() {
+        let uptime = Arc::new(FakeUptime::new());
+        let watch = Watch::new(uptime.clone());
+        let datetime = DateTime::new(2021, Month::January, 8, 10, 39, 27);
+        let hour = TimeSpan::<TestTick>::from_hours(1);
+        let rtc = DateTime::new(2021, Month::January, 1, 0, 0, 0);
+
+        assert!(watch.persist(rtc).is_err());
+        watch.set(datetime, TimeSpan::ZERO);
+        watch.set(datetime + hour, slow(hour, 20_000));
+        uptime.set(slow(hour, 20_000) + TimeSpan::from_millis(500));
+        let state = watch.persist(rtc).unwrap();
+
+        // The device is reset and boots 100 seconds later according to the RTC.
+        let uptime = Arc::new(FakeUptime::<TestTick>::new());
+        let restored = Watch::new(uptime.clone());
+        let upstamp = TimeSpan::from_secs(3);
+        uptime.set(upstamp);
+        restored
+            .restore(&state, rtc + TimeSpan::<TestTick>::from_secs(100), upstamp)
+            .unwrap();
+
+        let expected = to_wall(datetime + hour) + TimeSpan::from_millis(100_500);
+        let error = restored.wall_at(upstamp).unwrap() - expected;
+        assert!(error.abs() < TimeSpan::from_millis(1));
+        assert!((restored.drift_ppm() - 20.0).abs() < 0.1);
+        // The source of the persisted time is kept.
+        assert_eq!(
+            TimeSourceKind::Manual,
+            restored.now_with_quality().unwrap().1.source
+        );
+    }
 }